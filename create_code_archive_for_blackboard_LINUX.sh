@@ -7,6 +7,7 @@ zip -r source.zip \
 	Cargo.toml \
 	src \
 	shaders \
+	scenes \
 	resources/* \
 	-x"resources/helicopter.obj" \
 	-x"resources/lunarsurface.obj" \
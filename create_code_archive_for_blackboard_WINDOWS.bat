@@ -7,6 +7,7 @@ if exist source.zip (
     Cargo.toml ^
     src ^
     shaders ^
+    scenes ^
     resources/* ^
     -x!resources/helicopter.obj ^
     -x!resources/lunarsurface.obj ^
@@ -0,0 +1,23 @@
+#pragma once
+// Shared by every shader. The #version line is added by the shader builder.
+
+#define MAX_LIGHTS 4
+
+// Vertex shader inputs are matched to the attributes of meshes by name: position, normal,
+// color and uv, see `ATTRIBUTES` in src/vertex_layout.rs
+
+struct Light {
+    vec4 position; // w is 0 for directional lights
+    vec4 color;
+};
+
+// Updated once per frame and shared by all programs, see `FrameData` in src/frame.rs
+layout(std140, binding = 0) uniform FrameData {
+    mat4 transform; // projection * view
+    mat4 view;
+    mat4 projection;
+    vec2 resolution;
+    float time; // seconds, stopping while paused
+    int light_count;
+    Light lights[MAX_LIGHTS];
+};
@@ -1,16 +1,12 @@
-#version 430 core
+#include "common.glsl"
 
 out vec4 color;
 in vec4 vert_color;
 in noperspective vec4 vert_position;
 
-// Uniform variables that need to be updated in the rendering loop
-uniform layout(location=1) float time;
-
 /* uses screen coordinates to draw checkerboard, does not follow perspective
 void main()
 {
-    float time_dump = time; // just use `time`somewhere so that it doesn't crash
     vec4 color_1 = vec4(0.6f, 0.1f, 0.2f, 1.0f);
     vec4 color_2 = vec4(1.0f, 1.0f, 1.0f, 1.0f);
     
@@ -27,7 +23,6 @@ void main()
 void main()
 {
     //vec4 color;
-    float time_dump = time; // just use `time` somewhere so that it doesn't crash
     vec4 color_1 = vec4(0.6f, 0.1f, 0.2f, 1.0f);
     vec4 color_2 = vec4(1.0f, 1.0f, 1.0f, 1.0f);
     
@@ -1,15 +1,10 @@
-#version 430 core
+#include "common.glsl"
 
 out vec4 color;
 in vec4 vert_color;
 
-// Uniform variables that need to be updated in the rendering loop
-uniform layout(location=1) float time;
-
-
 void main()
 {
-    float time_dump = time; // just use `time`somewhere so that it doesn't crash
     vec4 color_1 = vec4(0.6f, 0.1f, 0.2f, 1.0f);
     vec4 color_2 = vec4(1.0f, 1.0f, 1.0f, 1.0f);
 
@@ -1,4 +1,4 @@
-#version 430 core
+#include "common.glsl"
 
 in vec4 vert_color;
 out vec4 color;
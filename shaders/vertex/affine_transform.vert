@@ -1,13 +1,12 @@
-#version 430 core
+#include "common.glsl"
 
 
 in vec4 position;
-uniform layout(location=1) float time;
-layout(location = 2) in vec4 color;
+in vec4 color;
 out vec4 vert_color;
 
 void main()
-{   float time_dump = time;
+{
 
 
     float a = 1;
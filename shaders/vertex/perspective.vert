@@ -1,15 +1,11 @@
-#version 430 core
+#include "common.glsl"
 
 in vec4 position;
-layout(location = 2) in vec4 color;
+in vec4 color;
 out vec4 vert_color;
 
-// Uniform variables that need to be updated in the rendering loop
-uniform layout(location=1) float time;
-uniform layout(location=3) mat4 transform;
-
 void main()
-{       float time_dump = time;
+{
         float d = -0.8;           // Distance for the perspective projection  
 
         // Perspective projection matrix
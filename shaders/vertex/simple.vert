@@ -1,18 +1,17 @@
-#version 430 core
+#include "common.glsl"
 
 in vec4 position;
-in layout(location = 2) vec4 color;
+in vec4 color;
 out vec4 vert_color;
 out vec4 vert_position;
 
-// Uniform variables that need to be updated in the rendering loop
-uniform layout(location=1) float time;
-uniform layout(location=3) mat4 transform;
+// The world transform of the scene node being drawn
+uniform mat4 model;
 
 void main()
-{       float time_dump = time;
+{
         
-        vec4 new_position = transform * position;
+        vec4 new_position = transform * model * position;
         
         vert_color = color;
         vert_position = new_position;
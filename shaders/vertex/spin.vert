@@ -1,13 +1,12 @@
-#version 430 core
+#include "common.glsl"
 
 in vec4 position;
-uniform layout(location=1) float time;
-layout(location = 2) in vec4 color;
+in vec4 color;
 out vec4 vert_color;
 
 void main()
 {
-    float speed_factor = 3.0;   // the argument in the cos and sin is divided by this factor
+    float speed_factor = 0.5;   // the argument in the cos and sin is divided by this factor
     float time_slow = time/speed_factor;
 
     mat4 x_rotation = mat4(
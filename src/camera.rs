@@ -0,0 +1,288 @@
+use crate::util;
+use serde::{Deserialize, Serialize};
+
+/// How the camera responds to input.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CameraMode {
+    /// Move around freely, looking where the mouse points.
+    #[default]
+    Fly,
+    /// Turn around a point in front of the camera, and zoom towards it.
+    Orbit,
+    /// Like `Orbit`, but turning around the point on its own, for inspecting models.
+    Turntable,
+}
+
+impl CameraMode {
+    pub fn next(self) -> CameraMode {
+        match self {
+            CameraMode::Fly => CameraMode::Orbit,
+            CameraMode::Orbit => CameraMode::Turntable,
+            CameraMode::Turntable => CameraMode::Fly,
+        }
+    }
+}
+
+/// How the camera projects the scene onto the screen.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Projection {
+    /// Keep parallel lines parallel, for comparing sizes and lining things up.
+    pub orthographic: bool,
+    /// The vertical field of view in radians. Orthographic projections show as much as a
+    /// perspective one does at the distance the camera turns around, so zooming works for both.
+    pub fov_y: f32,
+    pub near: f32,
+    pub far: f32,
+    /// Leave out the far plane of perspective projections, so nothing is too far away to be
+    /// drawn. Orthographic projections always have one.
+    pub infinite_far: bool,
+    /// Map the near plane to depth 1 and the far plane to 0, and draw what is nearer by
+    /// keeping greater depths. See `set_depth_state`.
+    pub reversed_z: bool,
+}
+
+const MIN_FOV: f32 = 0.01;
+const MAX_FOV: f32 = 3.1;
+
+impl Projection {
+    /// The matrix from view coordinates to clip coordinates, for a viewport that is
+    /// `aspect_ratio` times wider than it is tall, with the camera turning around the point
+    /// `distance` in front of it.
+    pub fn matrix(&self, aspect_ratio: f32, distance: f32) -> glm::Mat4 {
+        let (fov_y, near, far) = (self.fov_y, self.near, self.far);
+        // Reversed-Z starts from depths from 0 to 1, instead of the usual -1 to 1
+        let matrix = if self.orthographic {
+            let half_height = distance * (fov_y / 2.0).tan();
+            let half_width = half_height * aspect_ratio;
+            let (left, right, bottom, top) = (-half_width, half_width, -half_height, half_height);
+            if self.reversed_z {
+                glm::ortho_rh_zo(left, right, bottom, top, near, far)
+            } else {
+                glm::ortho(left, right, bottom, top, near, far)
+            }
+        } else if self.infinite_far {
+            if self.reversed_z {
+                glm::infinite_perspective_rh_zo(aspect_ratio, fov_y, near)
+            } else {
+                glm::infinite_perspective_rh_no(aspect_ratio, fov_y, near)
+            }
+        } else if self.reversed_z {
+            glm::perspective_rh_zo(aspect_ratio, fov_y, near, far)
+        } else {
+            glm::perspective(aspect_ratio, fov_y, near, far)
+        };
+
+        if self.reversed_z {
+            // Depth becomes 1 - depth
+            #[rustfmt::skip]
+            let reverse = glm::mat4(
+                1.0, 0.0, 0.0, 0.0,
+                0.0, 1.0, 0.0, 0.0,
+                0.0, 0.0, -1.0, 1.0,
+                0.0, 0.0, 0.0, 1.0,
+            );
+            reverse * matrix
+        } else {
+            matrix
+        }
+    }
+
+    /// Whether `0 < near < far`, which every projection matrix needs.
+    pub fn planes_valid(&self) -> bool {
+        0.0 < self.near && self.near < self.far
+    }
+
+    /// Change the field of view, keeping it between almost 0 and almost 180 degrees.
+    pub fn set_fov(&mut self, fov_y: f32) {
+        self.fov_y = fov_y.clamp(MIN_FOV, MAX_FOV);
+    }
+
+    /// Whether the context can draw with reversed-Z, which needs `glClipControl` from GL 4.5
+    /// or `ARB_clip_control`. Needs a current GL context.
+    pub unsafe fn reversed_z_supported() -> bool {
+        gl::ClipControl::is_loaded() && util::has_version_or_extension(4, 5, "GL_ARB_clip_control")
+    }
+
+    /// Set up clipping and depth testing for drawing with this projection, which differs
+    /// for reversed-Z. Call it once: the clip control stays changed for reversed-Z.
+    /// Needs a current GL context, and `reversed_z_supported` for reversed-Z.
+    pub unsafe fn set_depth_state(&self) {
+        if self.reversed_z {
+            gl::ClipControl(gl::LOWER_LEFT, gl::ZERO_TO_ONE);
+            gl::DepthFunc(gl::GREATER);
+            gl::ClearDepth(0.0);
+        } else {
+            // The default clip control, so older contexts without `glClipControl` work too
+            gl::DepthFunc(gl::LESS);
+            gl::ClearDepth(1.0);
+        }
+    }
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Projection {
+            orthographic: false,
+            fov_y: glm::half_pi(),
+            near: 0.25,
+            far: 100.0,
+            infinite_far: false,
+            reversed_z: false,
+        }
+    }
+}
+
+/// The views used in technical drawings, looking straight along an axis.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StandardView {
+    /// Looking along -z.
+    Front,
+    /// Looking along -x, at the right side.
+    Side,
+    /// Looking along -y, down from above.
+    Top,
+}
+
+/// What the user asked the camera to do this frame.
+#[derive(Clone, Copy, Debug)]
+pub struct CameraInput {
+    /// Movement to the right, up and backwards, from -1 to 1 along each axis.
+    pub movement: glm::Vec3,
+    /// How much to turn up and to the right, in radians.
+    pub look: glm::Vec2,
+    /// How many steps to zoom in, or out if negative. Flying does not zoom.
+    pub zoom: f32,
+    /// Distance moved per second.
+    pub speed: f32,
+}
+
+impl Default for CameraInput {
+    fn default() -> Self {
+        CameraInput {
+            movement: glm::zero(),
+            look: glm::zero(),
+            zoom: 0.0,
+            speed: 0.0,
+        }
+    }
+}
+
+/// Radians the turntable turns per second.
+const TURNTABLE_SPEED: f32 = 0.5;
+/// How much closer each zoom step gets to the point the camera turns around.
+const ZOOM_FACTOR: f32 = 0.9;
+const MIN_DISTANCE: f32 = 0.1;
+
+/// Where the camera is, where it looks, and how it projects the scene.
+///
+/// Every mode keeps the position and rotation up to date, so switching modes does not move
+/// the camera. The orbit and turntable modes turn around the point `distance` in front of it.
+#[derive(Clone, Debug)]
+pub struct Camera {
+    pub mode: CameraMode,
+    pub position: glm::Vec3,
+    /// Pitch, yaw and roll in radians.
+    pub rotation: glm::Vec3,
+    pub distance: f32,
+    pub projection: Projection,
+}
+
+impl Camera {
+    pub fn new(position: glm::Vec3, rotation: glm::Vec3) -> Camera {
+        Camera {
+            mode: CameraMode::Fly,
+            position,
+            rotation,
+            distance: 2.0,
+            projection: Projection::default(),
+        }
+    }
+
+    /// The rotation from world coordinates to view coordinates.
+    pub fn rotation_matrix(&self) -> glm::Mat4 {
+        glm::rotation(self.rotation.z, &glm::vec3(0.0, 0.0, 1.0))
+            * glm::rotation(self.rotation.x, &glm::vec3(1.0, 0.0, 0.0))
+            * glm::rotation(self.rotation.y, &glm::vec3(0.0, 1.0, 0.0))
+    }
+
+    /// The matrix from world coordinates to view coordinates.
+    pub fn view_matrix(&self) -> glm::Mat4 {
+        self.rotation_matrix() * glm::translation(&-self.position)
+    }
+
+    /// The matrix from view coordinates to clip coordinates.
+    pub fn projection_matrix(&self, aspect_ratio: f32) -> glm::Mat4 {
+        self.projection.matrix(aspect_ratio, self.distance)
+    }
+
+    /// The direction the camera looks in, in world coordinates.
+    pub fn forward(&self) -> glm::Vec3 {
+        self.to_world(&glm::vec3(0.0, 0.0, -1.0))
+    }
+
+    pub fn right(&self) -> glm::Vec3 {
+        self.to_world(&glm::vec3(1.0, 0.0, 0.0))
+    }
+
+    /// The point the orbit and turntable modes turn around.
+    pub fn target(&self) -> glm::Vec3 {
+        self.position + self.forward() * self.distance
+    }
+
+    /// Move and turn the camera as its mode does with `input`, over `delta_time` seconds.
+    pub fn update(&mut self, input: &CameraInput, delta_time: f32) {
+        let step = input.speed * delta_time;
+        match self.mode {
+            CameraMode::Fly => {
+                self.turn(&input.look);
+                // Up and down follow the world, so looking around does not change them
+                self.position += self.right() * input.movement.x * step
+                    + glm::vec3(0.0, 1.0, 0.0) * input.movement.y * step
+                    - self.forward() * input.movement.z * step;
+            }
+            CameraMode::Orbit | CameraMode::Turntable => {
+                // Moving sideways pans the target along, and moving back and forth zooms
+                let target = self.target()
+                    + self.right() * input.movement.x * step
+                    + glm::vec3(0.0, 1.0, 0.0) * input.movement.y * step;
+                self.distance = (self.distance * ZOOM_FACTOR.powf(input.zoom)
+                    + input.movement.z * step)
+                    .max(MIN_DISTANCE);
+
+                let mut look = input.look;
+                if self.mode == CameraMode::Turntable {
+                    look.y += TURNTABLE_SPEED * delta_time;
+                }
+                self.turn(&look);
+                // Turning moves the camera around the target instead of the target around it
+                self.position = target - self.forward() * self.distance;
+            }
+        }
+    }
+
+    /// Look at the point the camera turns around from one of the standard views, with an
+    /// orthographic projection.
+    pub fn view_from(&mut self, view: StandardView) {
+        let target = self.target();
+        self.rotation = match view {
+            StandardView::Front => glm::vec3(0.0, 0.0, 0.0),
+            StandardView::Side => glm::vec3(0.0, -glm::half_pi::<f32>(), 0.0),
+            StandardView::Top => glm::vec3(glm::half_pi(), 0.0, 0.0),
+        };
+        self.position = target - self.forward() * self.distance;
+        self.projection.orthographic = true;
+    }
+
+    /// Turn up and to the right, without looking past straight up or down.
+    fn turn(&mut self, look: &glm::Vec2) {
+        let pitch = self.rotation.x - look.x;
+        self.rotation.x = pitch.clamp(-glm::half_pi::<f32>(), glm::half_pi());
+        self.rotation.y = (self.rotation.y + look.y) % glm::two_pi::<f32>();
+    }
+
+    fn to_world(&self, direction: &glm::Vec3) -> glm::Vec3 {
+        (glm::transpose(&self.rotation_matrix()) * direction.to_homogeneous()).xyz()
+    }
+}
@@ -0,0 +1,474 @@
+use crate::camera::CameraMode;
+use std::fmt;
+
+pub const USAGE: &str = "\
+Usage:
+    gloom-rs [OPTIONS] [FILE]    Open a scene file (.toml) or a model (.obj)
+    gloom-rs check-shaders [DIR] Validate the shaders in DIR, ./shaders by default
+    gloom-rs check-scene FILE    Validate a scene file and the files it refers to
+    gloom-rs help                Print this message
+
+Without a file, ./scenes/default.toml is opened.
+
+Options:
+    --vertex PATH               Start with this vertex shader
+    --fragment PATH             Start with this fragment shader
+    --size WIDTHxHEIGHT         Window size, 800x800 by default
+    --fullscreen                Open a borderless fullscreen window
+    --no-vsync                  Draw frames as fast as possible
+    --no-grab                   Start with the cursor released, captured again with Tab
+    --input PATH                Key bindings, ./resources/input.toml by default
+    --msaa SAMPLES              Multisample anti-aliasing with 2, 4, 8 or 16 samples, off by default
+    --clear-color R,G,B[,A]     Background color, from 0 to 1
+    --camera-position X,Y,Z     Where the camera starts, instead of the scene's
+    --camera-rotation P,Y,R     Pitch, yaw and roll the camera starts with, in radians
+    --camera-mode MODE          fly, orbit or turntable, switched with C while running
+    --orthographic              Start with an orthographic projection, toggled with 5
+    --fov DEGREES               Vertical field of view, 90 by default
+    --near DISTANCE             Near plane, 0.25 by default
+    --far DISTANCE              Far plane, 100 by default
+    --infinite-far              Draw everything in front of the near plane
+    --reversed-z                Map the near plane to depth 1 and the far plane to 0
+    -h, --help                  Print this message
+
+Options can also be given as --name=value.";
+
+/// What the program was asked to do.
+#[derive(Clone, Debug)]
+pub enum Command {
+    /// Open the viewer.
+    View(ViewOptions),
+    /// Validate the shaders under a directory.
+    CheckShaders(String),
+    /// Validate a scene file.
+    CheckScene(String),
+    Help,
+}
+
+/// How the viewer is set up. Everything not given on the command line gets its default.
+#[derive(Clone, Debug)]
+pub struct ViewOptions {
+    /// A scene file, or an OBJ file to view on its own.
+    pub file: String,
+    pub vertex_shader: Option<String>,
+    pub fragment_shader: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub fullscreen: bool,
+    pub vsync: bool,
+    /// Capture and hide the cursor, so the mouse looks around.
+    pub grab_cursor: bool,
+    /// The key bindings file.
+    pub input: String,
+    /// 0 disables multisampling.
+    pub msaa_samples: u16,
+    pub clear_color: [f32; 4],
+    /// Overrides the camera of the scene file.
+    pub camera_position: Option<[f32; 3]>,
+    pub camera_rotation: Option<[f32; 3]>,
+    pub camera_mode: Option<CameraMode>,
+    /// Override the projection of the scene file, and leave it as it is if `false`.
+    pub orthographic: bool,
+    /// In degrees.
+    pub fov: Option<f32>,
+    pub near: Option<f32>,
+    pub far: Option<f32>,
+    pub infinite_far: bool,
+    pub reversed_z: bool,
+}
+
+impl Default for ViewOptions {
+    fn default() -> Self {
+        ViewOptions {
+            file: "./scenes/default.toml".to_string(),
+            vertex_shader: None,
+            fragment_shader: None,
+            width: 800,
+            height: 800,
+            fullscreen: false,
+            vsync: true,
+            grab_cursor: true,
+            input: "./resources/input.toml".to_string(),
+            msaa_samples: 0,
+            // Night sky, full opacity
+            clear_color: [0.035, 0.046, 0.078, 1.0],
+            camera_position: None,
+            camera_rotation: None,
+            camera_mode: None,
+            orthographic: false,
+            fov: None,
+            near: None,
+            far: None,
+            infinite_far: false,
+            reversed_z: false,
+        }
+    }
+}
+
+impl ViewOptions {
+    /// Whether `file` is a model rather than a scene file.
+    pub fn is_model(&self) -> bool {
+        self.file.to_lowercase().ends_with(".obj")
+    }
+}
+
+impl Command {
+    /// Parse the arguments, without the program name.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Command, CliError> {
+        let mut args = args.into_iter().peekable();
+        match args.peek().map(String::as_str) {
+            Some("check-shaders") => {
+                args.next();
+                subcommand_argument(args, Some("./shaders")).map(Command::CheckShaders)
+            }
+            Some("check-scene") => {
+                args.next();
+                subcommand_argument(args, None).map(Command::CheckScene)
+            }
+            Some("help") => Ok(Command::Help),
+            _ => Command::parse_view(args),
+        }
+    }
+
+    fn parse_view<I: Iterator<Item = String>>(mut args: I) -> Result<Command, CliError> {
+        let mut options = ViewOptions::default();
+        let mut file = None;
+
+        while let Some(arg) = args.next() {
+            if !arg.starts_with('-') {
+                if file.is_some() {
+                    return Err(CliError::UnexpectedArgument(arg));
+                }
+                file = Some(arg);
+                continue;
+            }
+
+            // `--name=value` is the same as `--name value`
+            let (name, mut inline_value) = match arg.find('=') {
+                Some(i) => (arg[..i].to_string(), Some(arg[i + 1..].to_string())),
+                None => (arg.clone(), None),
+            };
+            let mut value = || {
+                inline_value
+                    .take()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| CliError::MissingValue(name.clone()))
+            };
+            match name.as_str() {
+                "-h" | "--help" => return Ok(Command::Help),
+                "--vertex" => options.vertex_shader = Some(value()?),
+                "--fragment" => options.fragment_shader = Some(value()?),
+                "--size" => {
+                    let size = value()?;
+                    let (width, height) = parse_size(&size)
+                        .ok_or_else(|| invalid(&name, &size, "WIDTHxHEIGHT, like 1280x720"))?;
+                    options.width = width;
+                    options.height = height;
+                }
+                "--fullscreen" => options.fullscreen = true,
+                "--no-vsync" => options.vsync = false,
+                "--no-grab" => options.grab_cursor = false,
+                "--input" => options.input = value()?,
+                "--msaa" => {
+                    let samples = value()?;
+                    // glutin only takes powers of two, and 0 turns multisampling off
+                    options.msaa_samples = samples
+                        .trim()
+                        .parse()
+                        .ok()
+                        .filter(|&samples: &u16| samples == 0 || samples.is_power_of_two())
+                        .ok_or_else(|| invalid(&name, &samples, "0, 2, 4, 8 or 16"))?;
+                }
+                "--clear-color" => {
+                    let color = value()?;
+                    let components = parse_floats(&color)
+                        .filter(|components| components.len() == 3 || components.len() == 4)
+                        .ok_or_else(|| invalid(&name, &color, "R,G,B or R,G,B,A"))?;
+                    options.clear_color = [
+                        components[0],
+                        components[1],
+                        components[2],
+                        components.get(3).copied().unwrap_or(1.0),
+                    ];
+                }
+                "--camera-position" => {
+                    let position = value()?;
+                    options.camera_position = Some(
+                        parse_vec3(&position).ok_or_else(|| invalid(&name, &position, "X,Y,Z"))?,
+                    );
+                }
+                "--camera-rotation" => {
+                    let rotation = value()?;
+                    options.camera_rotation = Some(
+                        parse_vec3(&rotation)
+                            .ok_or_else(|| invalid(&name, &rotation, "PITCH,YAW,ROLL"))?,
+                    );
+                }
+                "--camera-mode" => {
+                    let mode = value()?;
+                    options.camera_mode = Some(match mode.as_str() {
+                        "fly" => CameraMode::Fly,
+                        "orbit" => CameraMode::Orbit,
+                        "turntable" => CameraMode::Turntable,
+                        _ => return Err(invalid(&name, &mode, "fly, orbit or turntable")),
+                    });
+                }
+                "--orthographic" => options.orthographic = true,
+                "--fov" => {
+                    let fov = value()?;
+                    options.fov = Some(
+                        parse_positive(&fov)
+                            .filter(|&fov| fov < 180.0)
+                            .ok_or_else(|| invalid(&name, &fov, "degrees between 0 and 180"))?,
+                    );
+                }
+                "--near" => {
+                    let near = value()?;
+                    options.near = Some(
+                        parse_positive(&near)
+                            .ok_or_else(|| invalid(&name, &near, "a positive distance"))?,
+                    );
+                }
+                "--far" => {
+                    let far = value()?;
+                    options.far = Some(
+                        parse_positive(&far)
+                            .ok_or_else(|| invalid(&name, &far, "a positive distance"))?,
+                    );
+                }
+                "--infinite-far" => options.infinite_far = true,
+                "--reversed-z" => options.reversed_z = true,
+                _ => return Err(CliError::UnknownOption(name)),
+            }
+            // Flags do not take values
+            if inline_value.is_some() {
+                return Err(CliError::UnexpectedValue(name));
+            }
+        }
+
+        if let Some(file) = file {
+            options.file = file;
+        }
+        Ok(Command::View(options))
+    }
+}
+
+/// The index of `selected` in `shaders`, appending it if it is not there yet.
+/// Without a selection the first shader is used.
+pub fn select_shader(shaders: &mut Vec<String>, selected: Option<&str>) -> usize {
+    let selected = match selected {
+        Some(selected) => selected,
+        None => return 0,
+    };
+    match shaders.iter().position(|shader| shader == selected) {
+        Some(index) => index,
+        None => {
+            shaders.push(selected.to_string());
+            shaders.len() - 1
+        }
+    }
+}
+
+/// The one argument of a subcommand, which falls back to `default` if there is one.
+fn subcommand_argument<I: Iterator<Item = String>>(
+    mut args: I,
+    default: Option<&str>,
+) -> Result<String, CliError> {
+    let argument = args
+        .next()
+        .or_else(|| default.map(str::to_string))
+        .ok_or_else(|| CliError::MissingValue("the subcommand".to_string()))?;
+    match args.next() {
+        Some(extra) => Err(CliError::UnexpectedArgument(extra)),
+        None => Ok(argument),
+    }
+}
+
+fn parse_size(size: &str) -> Option<(u32, u32)> {
+    let mut parts = size.splitn(2, 'x');
+    let width = parts.next()?.trim().parse().ok()?;
+    let height = parts.next()?.trim().parse().ok()?;
+    if width == 0 || height == 0 {
+        return None;
+    }
+    Some((width, height))
+}
+
+fn parse_positive(number: &str) -> Option<f32> {
+    number
+        .trim()
+        .parse()
+        .ok()
+        .filter(|&number: &f32| number > 0.0)
+}
+
+fn parse_floats(list: &str) -> Option<Vec<f32>> {
+    list.split(',')
+        .map(|value| value.trim().parse().ok())
+        .collect()
+}
+
+fn parse_vec3(list: &str) -> Option<[f32; 3]> {
+    match parse_floats(list)?.as_slice() {
+        &[x, y, z] => Some([x, y, z]),
+        _ => None,
+    }
+}
+
+fn invalid(option: &str, value: &str, expected: &'static str) -> CliError {
+    CliError::InvalidValue {
+        option: option.to_string(),
+        value: value.to_string(),
+        expected,
+    }
+}
+
+/// Everything that can be wrong with the arguments.
+#[derive(Debug)]
+pub enum CliError {
+    UnknownOption(String),
+    /// An option that takes a value was the last argument.
+    MissingValue(String),
+    /// A flag was given a value with `--flag=value`.
+    UnexpectedValue(String),
+    InvalidValue {
+        option: String,
+        value: String,
+        expected: &'static str,
+    },
+    /// More than one file, or an argument after a subcommand's.
+    UnexpectedArgument(String),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::UnknownOption(option) => write!(f, "Unknown option {}", option),
+            CliError::MissingValue(option) => write!(f, "Missing a value for {}", option),
+            CliError::UnexpectedValue(option) => write!(f, "{} does not take a value", option),
+            CliError::InvalidValue {
+                option,
+                value,
+                expected,
+            } => write!(
+                f,
+                "Invalid value {} for {}, expected {}",
+                value, option, expected
+            ),
+            CliError::UnexpectedArgument(argument) => {
+                write!(f, "Unexpected argument {}", argument)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, CliError> {
+        Command::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn view(args: &[&str]) -> ViewOptions {
+        match parse(args) {
+            Ok(Command::View(options)) => options,
+            other => panic!("Expected to view, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn defaults_without_arguments() {
+        let options = view(&[]);
+        assert_eq!(options.file, "./scenes/default.toml");
+        assert_eq!((options.width, options.height), (800, 800));
+        assert_eq!(options.msaa_samples, 0);
+        assert!(options.vsync && options.grab_cursor);
+    }
+
+    #[test]
+    fn options_and_file() {
+        let options = view(&[
+            "--size",
+            "1280x720",
+            "--no-vsync",
+            "--fov=60",
+            "--clear-color",
+            "1,0.5,0",
+            "resources/lunarsurface.obj",
+        ]);
+        assert_eq!(options.file, "resources/lunarsurface.obj");
+        assert!(options.is_model());
+        assert_eq!((options.width, options.height), (1280, 720));
+        assert!(!options.vsync);
+        assert_eq!(options.fov, Some(60.0));
+        assert_eq!(options.clear_color, [1.0, 0.5, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn msaa_takes_zero_or_powers_of_two() {
+        assert_eq!(view(&["--msaa", "0"]).msaa_samples, 0);
+        assert_eq!(view(&["--msaa=4"]).msaa_samples, 4);
+        for samples in &["3", "6", "-2", "many"] {
+            match parse(&["--msaa", samples]) {
+                Err(CliError::InvalidValue { option, .. }) => assert_eq!(option, "--msaa"),
+                other => panic!("--msaa {} gave {:?}", samples, other),
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        assert!(matches!(
+            parse(&["--bogus"]),
+            Err(CliError::UnknownOption(_))
+        ));
+        assert!(matches!(
+            parse(&["--vertex"]),
+            Err(CliError::MissingValue(_))
+        ));
+        assert!(matches!(
+            parse(&["--fullscreen=yes"]),
+            Err(CliError::UnexpectedValue(_))
+        ));
+        assert!(matches!(
+            parse(&["--size", "0x600"]),
+            Err(CliError::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            parse(&["--fov", "180"]),
+            Err(CliError::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            parse(&["a.toml", "b.toml"]),
+            Err(CliError::UnexpectedArgument(_))
+        ));
+    }
+
+    #[test]
+    fn subcommands() {
+        assert!(matches!(
+            parse(&["check-shaders"]),
+            Ok(Command::CheckShaders(directory)) if directory == "./shaders"
+        ));
+        assert!(matches!(
+            parse(&["check-scene", "scenes/default.toml"]),
+            Ok(Command::CheckScene(file)) if file == "scenes/default.toml"
+        ));
+        assert!(matches!(
+            parse(&["check-scene"]),
+            Err(CliError::MissingValue(_))
+        ));
+        assert!(matches!(parse(&["help"]), Ok(Command::Help)));
+        assert!(matches!(parse(&["--help"]), Ok(Command::Help)));
+    }
+
+    #[test]
+    fn select_shader_appends_unknown_shaders() {
+        let mut shaders = vec!["a.vert".to_string(), "b.vert".to_string()];
+        assert_eq!(select_shader(&mut shaders, None), 0);
+        assert_eq!(select_shader(&mut shaders, Some("b.vert")), 1);
+        assert_eq!(select_shader(&mut shaders, Some("c.vert")), 2);
+        assert_eq!(shaders.len(), 3);
+    }
+}
@@ -0,0 +1,104 @@
+use std::time::Instant;
+
+/// Frames slower than this are simulated as if they took this long, so the simulation does
+/// not try to catch up on a long stall, like the window being dragged, all at once.
+const MAX_FRAME_TIME: f32 = 0.25;
+const MIN_SPEED: f32 = 1.0 / 64.0;
+const MAX_SPEED: f32 = 4.0;
+
+/// What happened since the previous frame.
+#[derive(Clone, Copy, Debug)]
+pub struct Tick {
+    /// Real seconds since the previous frame, for things that should not pause or slow down
+    /// with the simulation, like moving the camera.
+    pub real_delta: f32,
+    /// How many fixed steps the simulation should take this frame.
+    pub steps: u32,
+}
+
+/// Keeps simulated time, which advances in fixed steps so animations behave the same at any
+/// frame rate, and can be paused, slowed down and stepped through.
+pub struct Clock {
+    /// Simulated seconds per step.
+    step: f32,
+    /// Simulated seconds per real second.
+    speed: f32,
+    paused: bool,
+    /// Simulated seconds that have passed but are too few for a whole step yet.
+    accumulator: f32,
+    /// Steps to take on the next frame while paused.
+    requested_steps: u32,
+    /// Counted instead of adding up seconds, so the time does not drift from rounding.
+    total_steps: u64,
+    previous_frame: Instant,
+}
+
+impl Clock {
+    /// A running clock taking steps of `step` seconds, like `1.0 / 120.0`.
+    pub fn new(step: f32) -> Clock {
+        Clock {
+            step,
+            speed: 1.0,
+            paused: false,
+            accumulator: 0.0,
+            requested_steps: 0,
+            total_steps: 0,
+            previous_frame: Instant::now(),
+        }
+    }
+
+    /// Start a new frame. Call once per frame, then take `Tick::steps` steps of `step` seconds.
+    pub fn tick(&mut self) -> Tick {
+        let now = Instant::now();
+        let real_delta = now.duration_since(self.previous_frame).as_secs_f32();
+        self.previous_frame = now;
+
+        let steps = if self.paused {
+            std::mem::take(&mut self.requested_steps)
+        } else {
+            self.accumulator += real_delta.min(MAX_FRAME_TIME) * self.speed;
+            let steps = (self.accumulator / self.step) as u32;
+            self.accumulator -= steps as f32 * self.step;
+            steps
+        };
+        self.total_steps += steps as u64;
+        Tick { real_delta, steps }
+    }
+
+    /// Simulated seconds per step.
+    pub fn step(&self) -> f32 {
+        self.step
+    }
+
+    /// Simulated seconds since the clock started.
+    pub fn time(&self) -> f32 {
+        (self.total_steps as f64 * self.step as f64) as f32
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.accumulator = 0.0;
+        self.requested_steps = 0;
+    }
+
+    /// Pause, and take a single step on the next frame.
+    pub fn step_once(&mut self) {
+        if !self.paused {
+            self.set_paused(true);
+        }
+        self.requested_steps += 1;
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Run the simulation `speed` times as fast as real time, like 0.25 for slow motion.
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+    }
+}
@@ -0,0 +1,42 @@
+use crate::shader::uniform_block;
+
+/// The uniform buffer binding of `FrameData`, as in `layout(binding = 0)` in shaders/common.glsl.
+pub const FRAME_DATA_BINDING: u32 = 0;
+
+/// The size of the light array, `MAX_LIGHTS` in shaders/common.glsl.
+pub const MAX_LIGHTS: usize = 4;
+
+uniform_block! {
+    /// A point light, or a directional light if `position.w` is 0.
+    pub struct Light {
+        pub position: glm::Vec4,
+        pub color: glm::Vec4,
+    }
+}
+
+uniform_block! {
+    /// Everything that stays the same for every program during a frame.
+    /// Uploaded once per frame instead of setting uniforms on each program.
+    pub struct FrameData {
+        /// `projection * view`
+        pub transform: glm::Mat4,
+        pub view: glm::Mat4,
+        pub projection: glm::Mat4,
+        /// Window size in pixels
+        pub resolution: glm::Vec2,
+        /// Simulated seconds, which stop while paused
+        pub time: f32,
+        pub light_count: i32,
+        pub lights: [Light; MAX_LIGHTS],
+    }
+}
+
+impl Light {
+    /// A light with no effect, for the unused slots of the light array.
+    pub fn none() -> Light {
+        Light {
+            position: glm::zero(),
+            color: glm::zero(),
+        }
+    }
+}
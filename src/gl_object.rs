@@ -0,0 +1,184 @@
+use gl::types::{GLenum, GLint, GLintptr, GLsizei, GLsizeiptr, GLuint};
+use std::marker::PhantomData;
+
+// Objects created by these wrappers must only be used and deleted while their GL context is
+// current, so they are created with unsafe constructors on the render thread and kept there
+// by being neither `Send` nor `Sync`. Everything else about them is then safe to call.
+type NotSend = PhantomData<*const ()>;
+
+/// An owned GL buffer object, deleted when dropped.
+pub struct Buffer {
+    id: GLuint,
+    size: usize,
+    _not_send: NotSend,
+}
+
+impl Buffer {
+    /// An empty buffer. Needs a current GL context.
+    pub unsafe fn new() -> Buffer {
+        let mut id = 0;
+        gl::CreateBuffers(1, &mut id);
+        Buffer {
+            id,
+            size: 0,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// A buffer holding `data`, with a usage hint like `gl::STATIC_DRAW`.
+    /// Needs a current GL context.
+    pub unsafe fn with_data<T: Copy>(data: &[T], usage: GLenum) -> Buffer {
+        let mut buffer = Buffer::new();
+        buffer.set_data(data, usage);
+        buffer
+    }
+
+    /// Size of the data store in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Replace the data store with a new one holding `data`.
+    pub fn set_data<T: Copy>(&mut self, data: &[T], usage: GLenum) {
+        let size = std::mem::size_of_val(data);
+        unsafe {
+            gl::NamedBufferData(
+                self.id,
+                size as GLsizeiptr,
+                data.as_ptr() as *const _,
+                usage,
+            );
+        }
+        self.size = size;
+    }
+
+    /// Overwrite part of the data store, starting `offset` bytes in. Writing past the end
+    /// of the store is a GL error and changes nothing.
+    pub fn set_sub_data<T: Copy>(&self, offset: usize, data: &[T]) {
+        unsafe {
+            gl::NamedBufferSubData(
+                self.id,
+                offset as GLintptr,
+                std::mem::size_of_val(data) as GLsizeiptr,
+                data.as_ptr() as *const _,
+            );
+        }
+    }
+
+    /// Bind to an indexed target like `gl::UNIFORM_BUFFER` or `gl::SHADER_STORAGE_BUFFER`.
+    pub fn bind_base(&self, target: GLenum, index: GLuint) {
+        unsafe {
+            gl::BindBufferBase(target, index, self.id);
+        }
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.id);
+        }
+    }
+}
+
+/// An owned vertex array object, together with the buffers it reads from.
+pub struct VertexArray {
+    id: GLuint,
+    // Kept alive for as long as the vertex array can draw from them
+    vertex_buffers: Vec<Buffer>,
+    element_buffer: Option<Buffer>,
+    _not_send: NotSend,
+}
+
+impl VertexArray {
+    /// A vertex array without buffers. Needs a current GL context.
+    pub unsafe fn new() -> VertexArray {
+        let mut id = 0;
+        gl::CreateVertexArrays(1, &mut id);
+        VertexArray {
+            id,
+            vertex_buffers: vec![],
+            element_buffer: None,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Take ownership of a vertex buffer with `stride` bytes between vertices, and return
+    /// the binding index attributes read it through.
+    pub fn add_vertex_buffer(&mut self, buffer: Buffer, stride: usize) -> GLuint {
+        let binding = self.vertex_buffers.len() as GLuint;
+        unsafe {
+            gl::VertexArrayVertexBuffer(self.id, binding, buffer.id, 0, stride as GLsizei);
+        }
+        self.vertex_buffers.push(buffer);
+        binding
+    }
+
+    /// Take ownership of the buffer with the indices, replacing the previous one.
+    pub fn set_element_buffer(&mut self, buffer: Buffer) {
+        unsafe {
+            gl::VertexArrayElementBuffer(self.id, buffer.id);
+        }
+        self.element_buffer = Some(buffer);
+    }
+
+    /// Read the attribute at `location` from the buffer at `binding`, as `components` values
+    /// of `gl_type` starting `offset` bytes into each vertex. Shaders see floats, so
+    /// integer types are converted as they are.
+    pub fn attribute(
+        &mut self,
+        location: GLuint,
+        binding: GLuint,
+        components: GLint,
+        gl_type: GLenum,
+        offset: u32,
+    ) {
+        unsafe {
+            gl::VertexArrayAttribFormat(self.id, location, components, gl_type, gl::FALSE, offset);
+            gl::VertexArrayAttribBinding(self.id, location, binding);
+            gl::EnableVertexArrayAttrib(self.id, location);
+        }
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindVertexArray(self.id);
+        }
+    }
+}
+
+impl Drop for VertexArray {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.id);
+        }
+    }
+}
+
+/// An owned shader program object, deleted when dropped. Built by `shader::ShaderBuilder`.
+pub struct Program {
+    id: GLuint,
+    _not_send: NotSend,
+}
+
+impl Program {
+    /// An empty program to attach shaders to. Needs a current GL context.
+    pub unsafe fn new() -> Program {
+        Program {
+            id: gl::CreateProgram(),
+            _not_send: PhantomData,
+        }
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+}
+
+impl Drop for Program {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteProgram(self.id);
+        }
+    }
+}
@@ -0,0 +1,460 @@
+use glutin::event::{MouseButton, VirtualKeyCode};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt, fs, io,
+    path::Path,
+};
+
+/// Something the user can do with the keyboard, bound to keys by `Bindings`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    /// Move faster while held.
+    Sprint,
+    LookUp,
+    LookDown,
+    LookLeft,
+    LookRight,
+    NextModel,
+    PreviousModel,
+    NextFragmentShader,
+    PreviousFragmentShader,
+    NextVertexShader,
+    PreviousVertexShader,
+    NextCameraMode,
+    ToggleOrthographic,
+    FrontView,
+    SideView,
+    TopView,
+    WidenFov,
+    NarrowFov,
+    TogglePause,
+    /// Pause, and advance the simulation by a single step.
+    StepSimulation,
+    /// Halve the speed of the simulation, for slow motion.
+    SlowDown,
+    SpeedUp,
+    ResetSpeed,
+    ToggleWireframe,
+    /// Release the cursor to use it, or capture it again to look around.
+    ToggleCursorGrab,
+    SaveScene,
+    Quit,
+}
+
+impl Action {
+    /// The keys each action is bound to without a bindings file. `resources/input.toml` lists
+    /// them in comments, so keep it in sync.
+    fn default_keys(self) -> &'static [VirtualKeyCode] {
+        use VirtualKeyCode::*;
+        match self {
+            Action::MoveForward => &[W],
+            Action::MoveBackward => &[S],
+            Action::MoveLeft => &[A],
+            Action::MoveRight => &[D],
+            Action::MoveUp => &[Space],
+            Action::MoveDown => &[LControl],
+            Action::Sprint => &[LShift],
+            Action::LookUp => &[Up],
+            Action::LookDown => &[Down],
+            Action::LookLeft => &[Left],
+            Action::LookRight => &[Right],
+            Action::NextModel => &[L],
+            Action::PreviousModel => &[K],
+            Action::NextFragmentShader => &[I],
+            Action::PreviousFragmentShader => &[U],
+            Action::NextVertexShader => &[P],
+            Action::PreviousVertexShader => &[O],
+            Action::NextCameraMode => &[C],
+            Action::ToggleOrthographic => &[Numpad5, Key5],
+            Action::FrontView => &[Numpad1, Key1],
+            Action::SideView => &[Numpad3, Key3],
+            Action::TopView => &[Numpad7, Key7],
+            Action::WidenFov => &[Minus, NumpadSubtract],
+            Action::NarrowFov => &[Equals, NumpadAdd],
+            Action::TogglePause => &[Pause, Key0],
+            Action::StepSimulation => &[Period],
+            Action::SlowDown => &[LBracket],
+            Action::SpeedUp => &[RBracket],
+            Action::ResetSpeed => &[Back],
+            Action::ToggleWireframe => &[F],
+            Action::ToggleCursorGrab => &[Tab],
+            Action::SaveScene => &[F5],
+            Action::Quit => &[Escape, Q],
+        }
+    }
+
+    const ALL: [Action; 33] = [
+        Action::MoveForward,
+        Action::MoveBackward,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::Sprint,
+        Action::LookUp,
+        Action::LookDown,
+        Action::LookLeft,
+        Action::LookRight,
+        Action::NextModel,
+        Action::PreviousModel,
+        Action::NextFragmentShader,
+        Action::PreviousFragmentShader,
+        Action::NextVertexShader,
+        Action::PreviousVertexShader,
+        Action::NextCameraMode,
+        Action::ToggleOrthographic,
+        Action::FrontView,
+        Action::SideView,
+        Action::TopView,
+        Action::WidenFov,
+        Action::NarrowFov,
+        Action::TogglePause,
+        Action::StepSimulation,
+        Action::SlowDown,
+        Action::SpeedUp,
+        Action::ResetSpeed,
+        Action::ToggleWireframe,
+        Action::ToggleCursorGrab,
+        Action::SaveScene,
+        Action::Quit,
+    ];
+}
+
+impl fmt::Display for Action {
+    /// The name in bindings files, like `move_forward`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, c) in format!("{:?}", self).chars().enumerate() {
+            if c.is_uppercase() && i > 0 {
+                write!(f, "_")?;
+            }
+            write!(f, "{}", c.to_ascii_lowercase())?;
+        }
+        Ok(())
+    }
+}
+
+/// The layout of a bindings file. Every action lists its keys, by the names of
+/// `VirtualKeyCode`, and actions that are left out keep the default keys the file does not use.
+///
+/// ```toml
+/// [bindings]
+/// move_forward = ["W", "Up"]
+/// toggle_wireframe = ["F1"]
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct BindingsFile {
+    bindings: BTreeMap<Action, Vec<VirtualKeyCode>>,
+}
+
+/// Which action each key triggers. A key triggers at most one action.
+#[derive(Clone, Debug)]
+pub struct Bindings {
+    actions: HashMap<VirtualKeyCode, Action>,
+}
+
+impl Bindings {
+    /// Bind every action to its keys, failing if a key is given to more than one action.
+    pub fn new(keys: &BTreeMap<Action, Vec<VirtualKeyCode>>) -> Result<Bindings, InputError> {
+        let mut actions = HashMap::new();
+        for (&action, action_keys) in keys {
+            for &key in action_keys {
+                if let Some(&bound) = actions.get(&key) {
+                    if bound != action {
+                        return Err(InputError::Conflict {
+                            key,
+                            first: bound,
+                            second: action,
+                        });
+                    }
+                }
+                actions.insert(key, action);
+            }
+        }
+        Ok(Bindings { actions })
+    }
+
+    /// Load the bindings in a TOML file on top of the default ones. Only the keys in the file
+    /// can conflict: a default key the file gives to another action is dropped.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Bindings, InputError> {
+        let path = path.as_ref().display().to_string();
+        let text = fs::read_to_string(&path).map_err(|error| InputError::Read {
+            path: path.clone(),
+            error,
+        })?;
+        let file: BindingsFile =
+            toml::from_str(&text).map_err(|error| InputError::Parse { path, error })?;
+
+        let mut bindings = Bindings::new(&file.bindings)?;
+        for (action, keys) in default_keys() {
+            if file.bindings.contains_key(&action) {
+                continue;
+            }
+            for key in keys {
+                bindings.actions.entry(key).or_insert(action);
+            }
+        }
+        Ok(bindings)
+    }
+
+    /// The action `key` triggers, if any.
+    pub fn action(&self, key: VirtualKeyCode) -> Option<Action> {
+        self.actions.get(&key).copied()
+    }
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Bindings::new(&default_keys()).expect("The default bindings do not conflict")
+    }
+}
+
+fn default_keys() -> BTreeMap<Action, Vec<VirtualKeyCode>> {
+    Action::ALL
+        .iter()
+        .map(|&action| (action, action.default_keys().to_vec()))
+        .collect()
+}
+
+/// What the mouse did since the last frame, collected from the window events by the event
+/// loop and taken by the render thread once per frame.
+#[derive(Clone, Debug, Default)]
+pub struct MouseState {
+    /// Movement in pixels, which keeps going when the cursor is grabbed.
+    pub delta: (f32, f32),
+    /// Lines scrolled, positive away from the user.
+    pub wheel: f32,
+    /// Where the cursor is in the window, in physical pixels from the top left corner.
+    /// `None` while it is outside the window.
+    pub cursor: Option<(f32, f32)>,
+    /// The buttons held down.
+    pub buttons: Vec<MouseButton>,
+}
+
+impl MouseState {
+    /// Everything the mouse did since the last call, starting over on the movement and
+    /// scrolling while keeping the cursor and buttons.
+    pub fn take_frame(&mut self) -> MouseState {
+        let frame = self.clone();
+        self.delta = (0.0, 0.0);
+        self.wheel = 0.0;
+        frame
+    }
+
+    pub fn press(&mut self, button: MouseButton) {
+        if !self.buttons.contains(&button) {
+            self.buttons.push(button);
+        }
+    }
+
+    pub fn release(&mut self, button: MouseButton) {
+        self.buttons.retain(|&held| held != button);
+    }
+}
+
+/// The actions whose keys are held, tracked from one frame to the next so presses can be told
+/// apart from holding a key down, and the mouse buttons held.
+pub struct Input {
+    bindings: Bindings,
+    held: HashSet<Action>,
+    previously_held: HashSet<Action>,
+    buttons: HashSet<MouseButton>,
+}
+
+impl Input {
+    pub fn new(bindings: Bindings) -> Input {
+        Input {
+            bindings,
+            held: HashSet::new(),
+            previously_held: HashSet::new(),
+            buttons: HashSet::new(),
+        }
+    }
+
+    /// Start a new frame with `keys` and `buttons` held down. Call once per frame, before
+    /// asking about actions and buttons.
+    pub fn update(&mut self, keys: &[VirtualKeyCode], buttons: &[MouseButton]) {
+        self.previously_held = std::mem::take(&mut self.held);
+        self.held = keys
+            .iter()
+            .filter_map(|&key| self.bindings.action(key))
+            .collect();
+        self.buttons = buttons.iter().copied().collect();
+    }
+
+    /// Whether a key of `action` is held down, for things that happen every frame like moving.
+    pub fn held(&self, action: Action) -> bool {
+        self.held.contains(&action)
+    }
+
+    /// Whether a key of `action` went down this frame, for things that happen once per press.
+    pub fn pressed(&self, action: Action) -> bool {
+        self.held.contains(&action) && !self.previously_held.contains(&action)
+    }
+
+    pub fn button_held(&self, button: MouseButton) -> bool {
+        self.buttons.contains(&button)
+    }
+}
+
+/// Everything that can go wrong while loading key bindings.
+#[derive(Debug)]
+pub enum InputError {
+    Read {
+        path: String,
+        error: io::Error,
+    },
+    Parse {
+        path: String,
+        error: toml::de::Error,
+    },
+    /// A key is bound to two actions.
+    Conflict {
+        key: VirtualKeyCode,
+        first: Action,
+        second: Action,
+    },
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InputError::Read { path, error } => {
+                write!(f, "Failed to read key bindings {}: {}", path, error)
+            }
+            InputError::Parse { path, error } => {
+                write!(f, "Failed to parse key bindings {}: {}", path, error)
+            }
+            InputError::Conflict { key, first, second } => write!(
+                f,
+                "The key {:?} is bound to both {} and {}",
+                key, first, second
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use VirtualKeyCode::*;
+
+    /// Load bindings from a temporary file with `text` in it.
+    fn load(name: &str, text: &str) -> Result<Bindings, InputError> {
+        let path = std::env::temp_dir().join(format!("gloom-rs-{}.toml", name));
+        fs::write(&path, text).unwrap();
+        let bindings = Bindings::load(&path);
+        fs::remove_file(&path).unwrap();
+        bindings
+    }
+
+    #[test]
+    fn default_bindings() {
+        let bindings = Bindings::default();
+        assert_eq!(bindings.action(W), Some(Action::MoveForward));
+        assert_eq!(bindings.action(Key5), Some(Action::ToggleOrthographic));
+        assert_eq!(bindings.action(Numpad5), Some(Action::ToggleOrthographic));
+        assert_eq!(bindings.action(F12), None);
+    }
+
+    #[test]
+    fn file_replaces_the_keys_of_its_actions() {
+        let bindings = load("replace", "[bindings]\nmove_forward = [\"Up\", \"F1\"]\n").unwrap();
+        assert_eq!(bindings.action(Up), Some(Action::MoveForward));
+        assert_eq!(bindings.action(F1), Some(Action::MoveForward));
+        // The old key of the action is gone, and the default action of the new key too
+        assert_eq!(bindings.action(W), None);
+        assert_eq!(bindings.action(S), Some(Action::MoveBackward));
+    }
+
+    #[test]
+    fn empty_file_keeps_the_defaults() {
+        let bindings = load("empty", "").unwrap();
+        assert_eq!(bindings.action(W), Some(Action::MoveForward));
+        assert_eq!(bindings.action(Escape), Some(Action::Quit));
+    }
+
+    #[test]
+    fn keys_bound_twice_in_the_file_conflict() {
+        let text = "[bindings]\nquit = [\"X\"]\nsave_scene = [\"X\"]\n";
+        match load("conflict", text) {
+            Err(InputError::Conflict { key, .. }) => assert_eq!(key, X),
+            other => panic!("Expected a conflict, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unknown_actions_and_keys_are_errors() {
+        assert!(matches!(
+            load("unknown-action", "[bindings]\nfly_away = [\"W\"]\n"),
+            Err(InputError::Parse { .. })
+        ));
+        assert!(matches!(
+            load("unknown-key", "[bindings]\nquit = [\"NoSuchKey\"]\n"),
+            Err(InputError::Parse { .. })
+        ));
+        assert!(matches!(
+            Bindings::load("does/not/exist.toml"),
+            Err(InputError::Read { .. })
+        ));
+    }
+
+    #[test]
+    fn shipped_file_lists_the_defaults() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/input.toml");
+        let text = fs::read_to_string(path).unwrap();
+        let commented: String = text
+            .lines()
+            .filter_map(|line| line.strip_prefix("# "))
+            .filter(|line| line.contains(" = ["))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        let file: BindingsFile = toml::from_str(&format!("[bindings]\n{}", commented)).unwrap();
+        assert_eq!(file.bindings, default_keys());
+        assert!(Bindings::load(path).is_ok());
+    }
+
+    #[test]
+    fn action_names() {
+        assert_eq!(Action::MoveForward.to_string(), "move_forward");
+        assert_eq!(Action::Quit.to_string(), "quit");
+    }
+
+    #[test]
+    fn pressed_only_on_the_first_frame() {
+        let mut input = Input::new(Bindings::default());
+        input.update(&[Tab], &[]);
+        assert!(input.pressed(Action::ToggleCursorGrab));
+        assert!(input.held(Action::ToggleCursorGrab));
+
+        input.update(&[Tab], &[]);
+        assert!(!input.pressed(Action::ToggleCursorGrab));
+        assert!(input.held(Action::ToggleCursorGrab));
+
+        input.update(&[], &[MouseButton::Left]);
+        assert!(!input.held(Action::ToggleCursorGrab));
+        assert!(input.button_held(MouseButton::Left));
+
+        input.update(&[Tab], &[]);
+        assert!(input.pressed(Action::ToggleCursorGrab));
+    }
+
+    #[test]
+    fn either_key_of_an_action_holds_it() {
+        let mut input = Input::new(Bindings::default());
+        input.update(&[Key5], &[]);
+        input.update(&[Key5, Numpad5], &[]);
+        assert!(!input.pressed(Action::ToggleOrthographic));
+        input.update(&[Numpad5], &[]);
+        assert!(!input.pressed(Action::ToggleOrthographic));
+        assert!(input.held(Action::ToggleOrthographic));
+    }
+}
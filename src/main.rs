@@ -8,43 +8,49 @@
 #![allow(unused_variables)]
 */
 extern crate nalgebra_glm as glm;
+use std::rc::Rc;
 use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
 use std::{mem, os::raw::c_void, ptr};
 
+mod camera;
+mod cli;
+mod clock;
+mod frame;
+mod gl_object;
+mod input;
+mod mesh;
 mod obj_parser;
+mod scene;
 mod shader;
+mod surface;
+mod terrain;
 mod util;
+mod vertex_layout;
 
-use glm::{pi, vec3, Mat4x4};
+use gl_object::Buffer;
+use glm::{pi, vec2, vec3, Mat4x4};
 use glutin::event::{
     DeviceEvent,
     ElementState::{Pressed, Released},
-    Event, KeyboardInput,
-    VirtualKeyCode::{self, *},
+    Event, KeyboardInput, MouseButton, MouseScrollDelta,
+    VirtualKeyCode,
     WindowEvent,
 };
 use glutin::event_loop::ControlFlow;
+use input::Action;
+use mesh::{Mesh, Primitive};
+use scene::SceneFile;
+use vertex_layout::{VertexAttribute, VertexLayout, VertexStream};
 
-// initial window size
-const INITIAL_SCREEN_W: u32 = 800;
-const INITIAL_SCREEN_H: u32 = 800;
 const MOVEMENT_SPEED: f32 = 2.0;
+// Seconds per step of the simulation, which animates the scene independently of the frame rate
+const SIMULATION_STEP: f32 = 1.0 / 120.0;
+// Radians per second the node named "rotor" spins
+const ROTOR_SPEED: f32 = 2.0;
 
 // == // Helper functions to make interacting with OpenGL a little bit prettier. You *WILL* need these! // == //
 
-// Get the size of an arbitrary array of numbers measured in bytes
-// Example usage:  pointer_to_array(my_array)
-fn byte_size_of_array<T>(val: &[T]) -> isize {
-    std::mem::size_of_val(&val[..]) as isize
-}
-
-// Get the OpenGL-compatible pointer to an arbitrary array of numbers
-// Example usage:  pointer_to_array(my_array)
-fn pointer_to_array<T>(val: &[T]) -> *const c_void {
-    &val[0] as *const T as *const c_void
-}
-
 // Get the size of the given type in bytes
 // Example usage:  size_of::<u64>()
 fn size_of<T>() -> i32 {
@@ -61,105 +67,151 @@ fn offset<T>(n: u32) -> *const c_void {
 // ptr::null()
 
 // == // Generate your VAO here
-unsafe fn create_vao(vertices: &Vec<f32>, indices: &Vec<u32>, colors: &Vec<f32>) -> u32 {
-    // Generate array & store ID
-    let mut vao_id: u32 = 0;
-    gl::GenVertexArrays(1, &mut vao_id);
-
-    // Bind VAO
-    gl::BindVertexArray(vao_id);
-
-    // Generate buffer & store ID
-    let mut vbo_id: u32 = 0;
-    gl::GenBuffers(1, &mut vbo_id);
-
-    // Bind VBO
-    gl::BindBuffer(gl::ARRAY_BUFFER, vbo_id);
-
-    // Fill VBO
-    gl::BufferData(
-        gl::ARRAY_BUFFER,
-        byte_size_of_array(vertices),
-        pointer_to_array(vertices),
-        gl::STATIC_DRAW,
-    );
-
-    // Setup VAP (clean this up?)
-    let attribute_index = 0;
-    gl::VertexAttribPointer(attribute_index, 4, gl::FLOAT, gl::FALSE, 0, ptr::null());
-
-    // Enable VBO
-    gl::EnableVertexAttribArray(attribute_index);
-
-    // Generate index buffer & ID
-    let mut ibo_id: u32 = 0;
-    gl::GenBuffers(1, &mut ibo_id);
-
-    // Bind IBO
-    gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ibo_id);
-
-    // Fill IBO
-    gl::BufferData(
-        gl::ELEMENT_ARRAY_BUFFER,
-        byte_size_of_array(indices),
-        pointer_to_array(indices),
-        gl::STATIC_DRAW,
-    );
-
-    let mut color_id: u32 = 0;
-    gl::GenBuffers(1, &mut color_id);
-
-    gl::BindBuffer(gl::ARRAY_BUFFER, color_id);
-
-    gl::BufferData(
-        gl::ARRAY_BUFFER,
-        byte_size_of_array(colors),
-        pointer_to_array(colors),
-        gl::STATIC_DRAW,
-    );
-
-    let color_attribute = 2;
-    gl::VertexAttribPointer(color_attribute, 4, gl::FLOAT, gl::FALSE, 0, ptr::null());
-    gl::EnableVertexAttribArray(color_attribute);
-
-    vao_id
+unsafe fn create_mesh(vertices: &Vec<f32>, indices: &Vec<u32>, colors: &Vec<f32>) -> Mesh {
+    // Positions and colors are 4 floats each, in separate buffers, matched to the shader
+    // inputs by name
+    let layout = VertexLayout::new()
+        .stream(VertexStream::per_vertex().attribute(VertexAttribute::float("position", 4)))
+        .stream(VertexStream::per_vertex().attribute(VertexAttribute::float("color", 4)));
+
+    // The mesh owns the vertex array and its buffers, and deletes them together with itself
+    let vao = layout.vertex_array(vec![
+        Buffer::with_data(vertices, gl::STATIC_DRAW),
+        Buffer::with_data(colors, gl::STATIC_DRAW),
+    ]);
+    Mesh::new(vao, indices, Primitive::Triangles)
+}
+
+// Confine and hide the cursor so the mouse can look around, or give it back
+fn set_cursor_grabbed(window: &glutin::window::Window, grabbed: bool) {
+    use glutin::window::CursorGrabMode;
+    let result = if grabbed {
+        // Some platforms cannot confine the cursor, but can lock it in place instead
+        window
+            .set_cursor_grab(CursorGrabMode::Confined)
+            .or_else(|_| window.set_cursor_grab(CursorGrabMode::Locked))
+    } else {
+        window.set_cursor_grab(CursorGrabMode::None)
+    };
+    if let Err(e) = result {
+        eprintln!("Failed to change the cursor grab: {}", e);
+    }
+    window.set_cursor_visible(!grabbed);
 }
 
 fn main() {
-    let x_axis: glm::Vec3 = glm::vec3(1.0, 0.0, 0.0);
-    let y_axis: glm::Vec3 = glm::vec3(0.0, 1.0, 0.0);
-    let z_axis: glm::Vec3 = glm::vec3(0.0, 0.0, 1.0);
-    let origin: glm::Vec3 = glm::vec3(0.0, 0.0, 0.0);
+    // The subcommands run without opening a window, see `gloom-rs help`
+    let options = match cli::Command::parse(std::env::args().skip(1)) {
+        Ok(cli::Command::View(options)) => options,
+        Ok(cli::Command::CheckShaders(directory)) => {
+            let ok = shader::check_shaders(std::path::Path::new(&directory));
+            std::process::exit(if ok { 0 } else { 1 });
+        }
+        Ok(cli::Command::CheckScene(path)) => {
+            std::process::exit(if scene::check_scene(&path) { 0 } else { 1 });
+        }
+        Ok(cli::Command::Help) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    // A model is viewed on its own, in a scene without a file to save it to
+    let scene_path = if options.is_model() {
+        None
+    } else {
+        Some(options.file.clone())
+    };
+    let mut scene_file = match &scene_path {
+        Some(path) => SceneFile::load(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }),
+        None => SceneFile::from_model(&options.file),
+    };
+    if scene_file.shaders.vertex.is_empty() || scene_file.shaders.fragment.is_empty() {
+        eprintln!(
+            "The scene file {} needs at least one vertex and one fragment shader",
+            options.file
+        );
+        std::process::exit(1);
+    }
+    if let Some(position) = options.camera_position {
+        scene_file.camera.position = position;
+    }
+    if let Some(rotation) = options.camera_rotation {
+        scene_file.camera.rotation = rotation;
+    }
+    if let Some(mode) = options.camera_mode {
+        scene_file.camera.mode = mode;
+    }
+    let projection = &mut scene_file.camera.projection;
+    projection.orthographic |= options.orthographic;
+    projection.infinite_far |= options.infinite_far;
+    projection.reversed_z |= options.reversed_z;
+    if let Some(fov) = options.fov {
+        projection.set_fov(fov.to_radians());
+    }
+    if let Some(near) = options.near {
+        projection.near = near;
+    }
+    if let Some(far) = options.far {
+        projection.far = far;
+    }
+    if !projection.planes_valid() {
+        eprintln!(
+            "Invalid camera: expected 0 < near < far, but near is {} and far is {}",
+            projection.near, projection.far
+        );
+        std::process::exit(1);
+    }
+
+    // Without a bindings file every action keeps its default keys
+    let bindings = if std::path::Path::new(&options.input).exists() {
+        input::Bindings::load(&options.input).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        })
+    } else {
+        input::Bindings::default()
+    };
+    // The event loop handles quitting, and the render thread everything else
+    let event_bindings = bindings.clone();
+
     // Set up the necessary objects to deal with windows and event handling
     let el = glutin::event_loop::EventLoop::new();
     let wb = glutin::window::WindowBuilder::new()
         .with_title("Gloom-rs")
         .with_resizable(true)
-        .with_inner_size(glutin::dpi::LogicalSize::new(
-            INITIAL_SCREEN_W,
-            INITIAL_SCREEN_H,
-        ));
-    let cb = glutin::ContextBuilder::new().with_vsync(true);
+        .with_inner_size(glutin::dpi::LogicalSize::new(options.width, options.height))
+        .with_fullscreen(if options.fullscreen {
+            Some(glutin::window::Fullscreen::Borderless(None))
+        } else {
+            None
+        });
+    let cb = glutin::ContextBuilder::new()
+        .with_vsync(options.vsync)
+        .with_multisampling(options.msaa_samples);
     let windowed_context = cb.build_windowed(wb, &el).unwrap();
-    // Uncomment these if you want to use the mouse for controls, but want it to be confined to the screen and/or invisible.
-    windowed_context
-        .window()
-        .set_cursor_grab(glutin::window::CursorGrabMode::Confined)
-        .expect("failed to grab cursor");
-    windowed_context.window().set_cursor_visible(false);
+    // The mouse looks around while the cursor is grabbed, which is toggled with Tab
+    set_cursor_grabbed(windowed_context.window(), options.grab_cursor);
 
     // Set up a shared vector for keeping track of currently pressed keys
     let arc_pressed_keys = Arc::new(Mutex::new(Vec::<VirtualKeyCode>::with_capacity(10)));
     // Make a reference of this vector to send to the render thread
     let pressed_keys = Arc::clone(&arc_pressed_keys);
 
-    // Set up shared tuple for tracking mouse movement between frames
-    let arc_mouse_delta = Arc::new(Mutex::new((0f32, 0f32)));
-    // Make a reference of this tuple to send to the render thread
-    let mouse_delta = Arc::clone(&arc_mouse_delta);
+    // Set up a shared state for tracking mouse movement, scrolling, buttons and the cursor
+    let arc_mouse_state = Arc::new(Mutex::new(input::MouseState::default()));
+    // Make a reference of this state to send to the render thread
+    let mouse_state = Arc::clone(&arc_mouse_state);
 
     // Set up shared tuple for tracking changes to the window size
-    let arc_window_size = Arc::new(Mutex::new((INITIAL_SCREEN_W, INITIAL_SCREEN_H, false)));
+    let arc_window_size = Arc::new(Mutex::new((options.width, options.height, false)));
     // Make a reference of this tuple to send to the render thread
     let window_size = Arc::clone(&arc_window_size);
 
@@ -171,17 +223,22 @@ fn main() {
         let context = unsafe {
             let c = windowed_context.make_current().unwrap();
             gl::load_with(|symbol| c.get_proc_address(symbol) as *const _);
+            shader::load_spirv_with(|symbol| c.get_proc_address(symbol) as *const _);
             c
         };
 
-        let mut window_aspect_ratio = INITIAL_SCREEN_W as f32 / INITIAL_SCREEN_H as f32;
+        let mut window_aspect_ratio = options.width as f32 / options.height as f32;
 
         // Set up openGL
         unsafe {
             gl::Enable(gl::DEPTH_TEST);
             gl::DepthFunc(gl::LESS);
             gl::Enable(gl::CULL_FACE);
-            gl::Disable(gl::MULTISAMPLE);
+            if options.msaa_samples > 0 {
+                gl::Enable(gl::MULTISAMPLE);
+            } else {
+                gl::Disable(gl::MULTISAMPLE);
+            }
             gl::Enable(gl::BLEND);
             gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
             gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
@@ -200,99 +257,138 @@ fn main() {
             );
         }
 
-        // == // Set up your VAO around here
-        let model_paths: Vec<String> = vec![
-            "./resources/cube.obj".to_string(),
-            "./resources/colored_panes.obj".to_string(),
-            "./resources/square.obj".to_string(),
-            "./resources/torus.obj".to_string(),
-            "./resources/full_square.obj".to_string(),
-            "./resources/monkey.obj".to_string(),
-        ];
-
-        let mut vaos: Vec<u32> = vec![];
-        let mut models: Vec<obj_parser::Parser> = vec![];
-        for path in model_paths {
-            let mut parser = obj_parser::Parser::new(&path);
-            let vertices = parser.flatten_vector(parser.vertices.clone());
-            let indices = parser.vertex_indices();
-            let colors = parser.flatten_vector(parser.colors.clone());
-            let vao;
-            unsafe {
-                vao = create_vao(&vertices, &indices, &colors);
-            }
-            vaos.push(vao);
-            models.push(parser);
-        }
-
-        let mut model_id: usize = 0;
-
         // == // Set up your shaders here
 
         // Basic usage of shader helper:
         // The example code below creates a 'shader' object.
-        // It contains the field `.program_id` and the method `.activate()`.
+        // It contains the methods `.program_id()` and `.activate()`.
         // The `.` in the path is relative to `Cargo.toml`.
         // This snippet is not, enough to do the exercise, and will need to be modified (outside
         // of just using the correct path), but it only needs to be called once
 
-        let fragment_shaders: Vec<String> = vec![
-            "./shaders/fragment/simple.frag".to_string(),
-            "./shaders/fragment/checkerboard.frag".to_string(),
-            "./shaders/fragment/circle.frag".to_string(),
-            "./shaders/fragment/sine.frag".to_string(),
-            "./shaders/fragment/spiral.frag".to_string(),
-            "./shaders/fragment/color_change.frag".to_string(),
-            "./shaders/fragment/triangle.frag".to_string(),
-        ];
-
-        let mut fragment_shader_id: usize = 0;
-
-        let vertex_shaders: Vec<String> = vec![
-            "./shaders/vertex/simple.vert".to_string(),
-            "./shaders/vertex/perspective.vert".to_string(),
-            "./shaders/vertex/mirror.vert".to_string(),
-            "./shaders/vertex/spin.vert".to_string(),
-            "./shaders/vertex/affine_transform.vert".to_string(),
-        ];
-
-        let mut vertex_shader_id: usize = 0;
-
-        // Used to demonstrate keyboard handling for exercise 2.
-        let mut model_changed = false;
-        let mut fragment_shader_changed = false;
-        let mut vertex_shader_changed = false;
-        let mut rebuild_shaders = true;
-
-        // Uniform variable(s) to be used in the shader
-        let mut time: f32 = 0.0;
-        let delta_t: f32 = 0.1; // amount to increase the time at each iteration
+        // The shaders to switch between come from the scene file, starting with the ones
+        // picked on the command line
+        let mut fragment_shader_id: usize = cli::select_shader(
+            &mut scene_file.shaders.fragment,
+            options.fragment_shader.as_deref(),
+        );
 
-        // The main rendering loop
-        let first_frame_time = std::time::Instant::now();
-        let mut previous_frame_time = first_frame_time;
+        let fragment_shaders: Vec<String> = scene_file.shaders.fragment.clone();
+
+        let mut vertex_shader_id: usize = cli::select_shader(
+            &mut scene_file.shaders.vertex,
+            options.vertex_shader.as_deref(),
+        );
+
+        let vertex_shaders: Vec<String> = scene_file.shaders.vertex.clone();
+
+        // Every combination of stages and defines is compiled once and reused
+        let mut shader_cache = shader::ShaderCache::new()
+            .include_path("./shaders")
+            .binary_cache("./target/shader_cache");
+        let mut shader_key = shader::ShaderKey::new(&[
+            &fragment_shaders[fragment_shader_id],
+            &vertex_shaders[vertex_shader_id],
+        ]);
+
+        // There is no previous program to fall back on yet, so the first one has to work
+        unsafe { shader_cache.get(&shader_key) }.unwrap_or_else(|e| panic!("{}", e));
+        let mut requested_key = shader_key.clone();
+
+        // == // Set up your VAO around here
+        // Only the meshes are kept, the parsed files are dropped once uploaded
+        let mut scene = scene_file
+            .build(|geometry| match geometry {
+                scene::Geometry::Model(path) => {
+                    let mut parser = obj_parser::Parser::new(&path.to_string());
+                    let vertices = parser.flatten_vector(parser.vertices.clone());
+                    let indices = parser.vertex_indices();
+                    let colors = parser.flatten_vector(parser.colors.clone());
+                    unsafe { Rc::new(create_mesh(&vertices, &indices, &colors)) }
+                }
+                scene::Geometry::Data(data) => unsafe { Rc::new(Mesh::from_data(&data)) },
+            })
+            .unwrap_or_else(|e| panic!("{}", e));
 
-        let mut camera_rotation: glm::Vec3 = glm::vec3(0.0, 0.0, 0.0);
-        let mut camera_position: glm::Vec3 = glm::vec3(0.0, 0.0, 2.0);
-        let mut sprint = false;
+        // Spun around every frame, if the scene has one
+        let rotor = scene.find("rotor");
+
+        // Materials with shaders of their own are built up front, and skipped while broken
+        for key in scene.shader_keys() {
+            if let Err(e) = unsafe { shader_cache.get(&key) } {
+                eprintln!("{}", e);
+            }
+        }
 
+        // Lights beyond `MAX_LIGHTS` are left out
+        let mut lights = [frame::Light::none(); frame::MAX_LIGHTS];
+        for (light, description) in lights.iter_mut().zip(&scene_file.lights) {
+            *light = frame::Light {
+                position: description.position.into(),
+                color: description.color.into(),
+            };
+        }
+        let light_count = scene_file.lights.len().min(frame::MAX_LIGHTS) as i32;
+
+        // `None` draws the whole scene, and `Some(i)` only the i-th of its roots
+        let mut model_id: Option<usize> = None;
+
+        let mut wireframe = false;
+        let mut cursor_grabbed = options.grab_cursor;
+        let mut rebuild_shaders = false;
+        let mut input = input::Input::new(bindings);
+
+        // Camera matrices, time and lights for every program, uploaded once per frame
+        let mut frame_data =
+            unsafe { shader::UniformBuffer::<frame::FrameData>::new(frame::FRAME_DATA_BINDING) };
+        // Simulated time, which can be paused and slowed down, uploaded as `time` in seconds
+        let mut clock = clock::Clock::new(SIMULATION_STEP);
+
+        let mut camera = scene_file.camera.camera();
+        // Reversed-Z changes how depth is tested and cleared, and stays for the whole run
+        if camera.projection.reversed_z && !unsafe { camera::Projection::reversed_z_supported() } {
+            eprintln!("Reversed-Z needs OpenGL 4.5 or the ARB_clip_control extension");
+            std::process::exit(1);
+        }
+        unsafe {
+            camera.projection.set_depth_state();
+        }
+
+        // The main rendering loop
         loop {
             if rebuild_shaders {
-                unsafe {
-                    shader::ShaderBuilder::new()
-                        .attach_file(fragment_shaders[fragment_shader_id].as_str())
-                        .attach_file(vertex_shaders[vertex_shader_id].as_str())
-                        .link()
-                        .activate();
+                requested_key = shader::ShaderKey::new(&[
+                    &fragment_shaders[fragment_shader_id],
+                    &vertex_shaders[vertex_shader_id],
+                ]);
+                if let Err(e) = unsafe { shader_cache.get(&requested_key) } {
+                    eprintln!("{}", e);
                 }
                 rebuild_shaders = false;
             }
 
-            // Compute time passed since the previous frame and since the start of the program
-            let now = std::time::Instant::now();
-            let elapsed = now.duration_since(first_frame_time).as_secs_f32();
-            let delta_time = now.duration_since(previous_frame_time).as_secs_f32();
-            previous_frame_time = now;
+            // Rebuild the variants whose source files were saved, and retry the broken ones
+            for error in unsafe { shader_cache.reload_changed() } {
+                eprintln!("{}", error);
+            }
+
+            // Keep drawing with the previous variant until the requested one builds
+            if requested_key != shader_key && shader_cache.contains(&requested_key) {
+                shader_key = requested_key.clone();
+                if let Err(e) = frame_data.verify(shader_cache.variant(&shader_key).unwrap()) {
+                    eprintln!("{}", e);
+                }
+            }
+            let shader = shader_cache
+                .variant(&shader_key)
+                .expect("The current shader variant has been built");
+            unsafe {
+                shader.activate();
+            }
+
+            // Compute real time passed since the previous frame, and how far to simulate
+            let tick = clock.tick();
+            let delta_time = tick.real_delta;
 
             // Handle resize events
             if let Ok(mut new_size) = window_size.lock() {
@@ -307,207 +403,200 @@ fn main() {
                 }
             }
 
-            // Update the uniform variables
-            unsafe {
-                time += delta_t; // Update the time value
-                gl::Uniform1f(1, time);
-            }
-
-            // We tried to change the model this loop.
-            let mut model_pressed = false;
-
-            // We tried to change the fragment shader this loop.
-            let mut fragment_shader_pressed = false;
-
-            let mut vertex_shader_pressed = false;
-
-            // Handle keyboard input
-
-            // Shader switching
-            /*
+            // Handle keyboard input, through the actions the held keys are bound to
+            let mouse = mouse_state
+                .lock()
+                .map(|mut state| state.take_frame())
+                .unwrap_or_default();
             if let Ok(keys) = pressed_keys.lock() {
-                for key in keys.iter() {
-                    match key {
-                        // The `VirtualKeyCode` enum is defined here:
-                        //    https://docs.rs/winit/0.25.0/winit/event/enum.VirtualKeyCode.html
-                        VirtualKeyCode::K => {
-                            if !model_changed {
-                                if model_id == 0 {
-                                    model_id = models.len();
-                                }
-                                model_id -= 1;
-                                model_id %= models.len();
-
-                                model_changed = true;
-                                //println!("{}", model_id);
-                            }
-                            model_pressed = true;            if let Ok()
-
-                        }
-                        VirtualKeyCode::L => {
-                            if !model_changed {
-                                model_id += 1;
-                                model_id %= models.len();
-                                model_changed = true;
-                                //println!("{}", model_id);
-                            }
-                            model_pressed = true;
-                        }
-                        VirtualKeyCode::U => {
-                            if !fragment_shader_changed {
-                                if fragment_shader_id == 0 {
-                                    fragment_shader_id = fragment_shaders.len();
-                                }
-                                fragment_shader_id =
-                                    (fragment_shader_id - 1) % fragment_shaders.len();
-                                rebuild_shaders = true;
-                                fragment_shader_changed = true;
-                            }
-                            fragment_shader_pressed = true;
-                        }
-                        VirtualKeyCode::I => {
-                            if !fragment_shader_changed {
-                                fragment_shader_id =
-                                    (fragment_shader_id + 1) % fragment_shaders.len();
-                                rebuild_shaders = true;
-                                fragment_shader_changed = true;
-                            }
-                            fragment_shader_pressed = true;
-                        }
-                        VirtualKeyCode::O => {
-                            if !vertex_shader_changed {
-                                if vertex_shader_id == 0 {
-                                    vertex_shader_id = vertex_shaders.len();
-                                }
-                                vertex_shader_id = (vertex_shader_id - 1) % vertex_shaders.len();
-                                rebuild_shaders = true;
-                                vertex_shader_changed = true;
-                            }
-                            vertex_shader_pressed = true;
-                        }
-                        VirtualKeyCode::P => {
-                            if !vertex_shader_changed {
-                                vertex_shader_id = (vertex_shader_id + 1) % vertex_shaders.len();
-                                vertex_shader_changed = true;
-                                rebuild_shaders = true;
-                            }
-                            vertex_shader_pressed = true;
-                        }
-
-                        // default handler:
-                        _ => {}            if let Ok()
+                input.update(&keys, &mouse.buttons);
+            }
+            if input.pressed(Action::ToggleCursorGrab) {
+                cursor_grabbed = !cursor_grabbed;
+                set_cursor_grabbed(context.window(), cursor_grabbed);
+            }
 
-                    }
-                }
+            // Time controls, which take effect from the next frame
+            if input.pressed(Action::TogglePause) {
+                clock.set_paused(!clock.is_paused());
+                println!("{}", if clock.is_paused() { "Paused" } else { "Resumed" });
+            }
+            if input.pressed(Action::StepSimulation) {
+                clock.step_once();
+            }
+            if input.pressed(Action::SlowDown) {
+                clock.set_speed(clock.speed() / 2.0);
+                println!("Simulation speed: {}x", clock.speed());
+            }
+            if input.pressed(Action::SpeedUp) {
+                clock.set_speed(clock.speed() * 2.0);
+                println!("Simulation speed: {}x", clock.speed());
+            }
+            if input.pressed(Action::ResetSpeed) {
+                clock.set_speed(1.0);
+                println!("Simulation speed: {}x", clock.speed());
+            }
 
-                if !model_pressed {
-                    model_changed = false;
+            // Advance the simulation in fixed steps, so it looks the same at any frame rate
+            for _ in 0..tick.steps {
+                if let Some(rotor) = rotor {
+                    let rotation = &mut scene[rotor].transform.rotation.y;
+                    *rotation = (*rotation + ROTOR_SPEED * clock.step()) % glm::two_pi::<f32>();
                 }
+            }
 
-                if !fragment_shader_pressed {
-                    fragment_shader_changed = false;
+            // Model and shader switching, once per key press
+            let model_count = scene.roots().len();
+            if input.pressed(Action::NextModel) {
+                model_id = match model_id {
+                    None if model_count > 0 => Some(0),
+                    Some(i) if i + 1 < model_count => Some(i + 1),
+                    _ => None,
+                };
+            }
+            if input.pressed(Action::PreviousModel) {
+                model_id = match model_id {
+                    None => model_count.checked_sub(1),
+                    Some(0) => None,
+                    Some(i) => Some(i - 1),
+                };
+            }
+            if input.pressed(Action::NextFragmentShader) {
+                fragment_shader_id = (fragment_shader_id + 1) % fragment_shaders.len();
+                rebuild_shaders = true;
+            }
+            if input.pressed(Action::PreviousFragmentShader) {
+                fragment_shader_id =
+                    (fragment_shader_id + fragment_shaders.len() - 1) % fragment_shaders.len();
+                rebuild_shaders = true;
+            }
+            if input.pressed(Action::NextVertexShader) {
+                vertex_shader_id = (vertex_shader_id + 1) % vertex_shaders.len();
+                rebuild_shaders = true;
+            }
+            if input.pressed(Action::PreviousVertexShader) {
+                vertex_shader_id =
+                    (vertex_shader_id + vertex_shaders.len() - 1) % vertex_shaders.len();
+                rebuild_shaders = true;
+            }
+            if input.pressed(Action::ToggleWireframe) {
+                wireframe = !wireframe;
+                unsafe {
+                    let mode = if wireframe { gl::LINE } else { gl::FILL };
+                    gl::PolygonMode(gl::FRONT_AND_BACK, mode);
                 }
+            }
 
-                if !vertex_shader_pressed {
-                    vertex_shader_changed = false;
-                }
+            if input.pressed(Action::NextCameraMode) {
+                camera.mode = camera.mode.next();
+                println!("Camera mode: {:?}", camera.mode);
+            }
+            if input.pressed(Action::ToggleOrthographic) {
+                camera.projection.orthographic = !camera.projection.orthographic;
+            }
+            if input.pressed(Action::FrontView) {
+                camera.view_from(camera::StandardView::Front);
+            }
+            if input.pressed(Action::SideView) {
+                camera.view_from(camera::StandardView::Side);
+            }
+            if input.pressed(Action::TopView) {
+                camera.view_from(camera::StandardView::Top);
+            }
+            const FOV_STEP: f32 = 5.0;
+            if input.pressed(Action::WidenFov) {
+                camera.projection.set_fov(camera.projection.fov_y + FOV_STEP.to_radians());
+                println!("Field of view: {:.0} degrees", camera.projection.fov_y.to_degrees());
             }
-            */
-            let perspective_matrix: Mat4x4 =
-                glm::perspective(window_aspect_ratio, glm::half_pi(), 0.25, 100.0);
+            if input.pressed(Action::NarrowFov) {
+                camera.projection.set_fov(camera.projection.fov_y - FOV_STEP.to_radians());
+                println!("Field of view: {:.0} degrees", camera.projection.fov_y.to_degrees());
+            }
+
+            let mut camera_input = camera::CameraInput::default();
 
             // Handle mouse movement. delta contains the x and y movement of the mouse since last frame in pixels
-            if let Ok(mut delta) = mouse_delta.lock() {
-                const X_SENSITIVITY: f32 = 60.0;
-                const Y_SENSITIVITY: f32 = 60.0;
-                // == // Optionally access the accumulated mouse movement between
-                // == // frames here with `delta.0` and `delta.1`
+            // A released cursor only looks around while dragging
+            if cursor_grabbed || input.button_held(MouseButton::Left) {
+                // Moving the mouse across the whole window turns half a circle, however long
+                // the frame took
+                const X_SENSITIVITY: f32 = 1.0;
+                const Y_SENSITIVITY: f32 = 1.0;
+                let delta = mouse.delta;
                 if let Ok(screen) = window_size.lock() {
-                    camera_rotation += vec3(
-                        delta.1 / screen.1 as f32 * pi::<f32>() * delta_time * X_SENSITIVITY,
-                        delta.0 / screen.0 as f32 * pi::<f32>() * delta_time * Y_SENSITIVITY,
-                        0.0,
+                    camera_input.look = vec2(
+                        -delta.1 / screen.1 as f32 * pi::<f32>() * X_SENSITIVITY,
+                        delta.0 / screen.0 as f32 * pi::<f32>() * Y_SENSITIVITY,
                     );
                 }
-                *delta = (0.0, 0.0); // reset when done
             }
+            camera_input.zoom = mouse.wheel;
 
-            camera_rotation.x = (glm::max(
-                &glm::min(&camera_rotation, glm::half_pi()),
-                -glm::half_pi::<f32>(),
-            ))
-            .x;
-
-            camera_rotation.y %= glm::two_pi::<f32>();
-
-            let inverse_rotation_matrix: Mat4x4 = glm::rotation(camera_rotation.x * -1.0, &x_axis)
-            * glm::rotation(camera_rotation.y * -1.0, &y_axis);
+            const SPRINT_MULTIPLIER: f32 = 4.0;
+            camera_input.speed = MOVEMENT_SPEED;
+            if input.held(Action::Sprint) {
+                camera_input.speed *= SPRINT_MULTIPLIER;
+            }
+            // Opposite actions cancel out
+            let axis = |positive: Action, negative: Action| {
+                input.held(positive) as i32 as f32 - input.held(negative) as i32 as f32
+            };
+            camera_input.movement = vec3(
+                axis(Action::MoveRight, Action::MoveLeft),
+                axis(Action::MoveUp, Action::MoveDown),
+                axis(Action::MoveBackward, Action::MoveForward),
+            );
 
-            if let Ok(keys) = pressed_keys.lock() {
-                const SPRINT_MULTIPLIER: f32 = 4.0;
-                let mut delta_speed = MOVEMENT_SPEED * delta_time;
-                if keys.contains(&LShift) {
-                    delta_speed *= SPRINT_MULTIPLIER;
-                }
-                const X_SENSITIVITY: f32 = 7.0;
-                const Y_SENSITIVITY: f32 = 7.0;
-                for key in keys.iter() {
-                    match key {
-                        D | L => {
-                            camera_position += (inverse_rotation_matrix
-                                * (x_axis.to_homogeneous() * delta_speed))
-                                .xyz()
-                        }
-                        A | J => {
-                            camera_position -= (inverse_rotation_matrix
-                                * (x_axis.to_homogeneous() * delta_speed))
-                                .xyz()
-                        }
-                        Space => camera_position += y_axis * delta_speed,
-                        LControl => camera_position -= y_axis * delta_speed,
-                        S | K => {
-                            camera_position += (inverse_rotation_matrix
-                                * (z_axis.to_homogeneous() * delta_speed))
-                                .xyz()
-                        }
-                        W | I => {
-                            camera_position -= (inverse_rotation_matrix
-                                * (z_axis.to_homogeneous() * delta_speed))
-                                .xyz()
-                        }
-                        Left => camera_rotation.y -= Y_SENSITIVITY * delta_time,
-                        Right => camera_rotation.y += Y_SENSITIVITY * delta_time,
-                        Up => camera_rotation.x -= X_SENSITIVITY * delta_time,
-                        Down => camera_rotation.x += X_SENSITIVITY * delta_time,
-                        _ => {}
-                    }
+            const X_SENSITIVITY: f32 = 7.0;
+            const Y_SENSITIVITY: f32 = 7.0;
+            camera_input.look += vec2(
+                axis(Action::LookUp, Action::LookDown) * X_SENSITIVITY * delta_time,
+                axis(Action::LookRight, Action::LookLeft) * Y_SENSITIVITY * delta_time,
+            );
+            camera.update(&camera_input, delta_time);
+
+            // Save the scene back to its file
+            if input.pressed(Action::SaveScene) {
+                scene_file.camera = scene::CameraDescription::from_camera(&camera);
+                scene_file.update_nodes(&scene);
+                match &scene_path {
+                    Some(path) => match scene_file.save(path) {
+                        Ok(()) => println!("Saved the scene to {}", path),
+                        Err(e) => eprintln!("{}", e),
+                    },
+                    None => println!("A model opened on its own has no scene file to save to"),
                 }
             }
-            let rotation_matrix: Mat4x4 = glm::rotation(camera_rotation.x, &x_axis)
-                * glm::rotation(camera_rotation.y, &y_axis);
 
             unsafe {
-            // Calculate transformations
-            let translation_matrix: Mat4x4 = glm::translation(&(camera_position * -1.0));
-
-            let transform_matrix: Mat4x4 =
-                perspective_matrix * rotation_matrix * translation_matrix;
-            // == // Please compute camera transforms here (exercise 2 & 3)
-
-                gl::UniformMatrix4fv(3, 1, gl::FALSE, transform_matrix.as_ptr());
-                //gl::UniformMatrix4fv(4, 1, gl::FALSE, rotation_matrix.as_ptr());
+                // Calculate transformations
+                let perspective_matrix: Mat4x4 = camera.projection_matrix(window_aspect_ratio);
+                let view_matrix: Mat4x4 = camera.view_matrix();
+                let transform_matrix: Mat4x4 = perspective_matrix * view_matrix;
+
+                let (width, height) = window_size
+                    .lock()
+                    .map_or((options.width, options.height), |size| (size.0, size.1));
+                // Shared by all programs, so switching shaders does not need any uploads
+                frame_data.update(&frame::FrameData {
+                    transform: transform_matrix,
+                    view: view_matrix,
+                    projection: perspective_matrix,
+                    resolution: glm::vec2(width as f32, height as f32),
+                    time: clock.time(),
+                    light_count,
+                    lights,
+                });
                 // Clear the color and depth buffers
-                gl::ClearColor(0.035, 0.046, 0.078, 1.0); // night sky, full opacity
-                                                          //gl::ClearColor(1.0, 1.0, 1.0, 1.0); // white background, full opacity
+                let [red, green, blue, alpha] = options.clear_color;
+                gl::ClearColor(red, green, blue, alpha);
                 gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
                 // == // Issue the necessary gl:: commands to draw your scene here
-                gl::BindVertexArray(vaos[model_id]);
-                gl::DrawElements(
-                    gl::TRIANGLES,
-                    models[model_id].vertex_indices().len() as i32,
-                    gl::UNSIGNED_INT,
-                    ptr::null(),
-                );
+                scene.update();
+                match model_id.and_then(|i| scene.roots().get(i)) {
+                    Some(&root) => scene.draw_node(root, &shader_cache, &shader_key),
+                    None => scene.draw(&shader_cache, &shader_key),
+                }
             }
 
             // Display the new color buffer on the display
@@ -591,15 +680,49 @@ fn main() {
                     }
                 }
 
-                // Handle Escape and Q keys separately
-                match keycode {
-                    Escape => {
-                        *control_flow = ControlFlow::Exit;
-                    }
-                    Q => {
-                        *control_flow = ControlFlow::Exit;
+                // Handle quitting separately
+                if event_bindings.action(keycode) == Some(Action::Quit) {
+                    *control_flow = ControlFlow::Exit;
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::MouseWheel { delta, .. },
+                ..
+            } => {
+                // Touchpads scroll in pixels, which are counted as lines of 20 pixels
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 20.0,
+                };
+                if let Ok(mut mouse) = arc_mouse_state.lock() {
+                    mouse.wheel += lines;
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::MouseInput { state, button, .. },
+                ..
+            } => {
+                if let Ok(mut mouse) = arc_mouse_state.lock() {
+                    match state {
+                        Pressed => mouse.press(button),
+                        Released => mouse.release(button),
                     }
-                    _ => {}
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CursorMoved { position, .. },
+                ..
+            } => {
+                if let Ok(mut mouse) = arc_mouse_state.lock() {
+                    mouse.cursor = Some((position.x as f32, position.y as f32));
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CursorLeft { .. },
+                ..
+            } => {
+                if let Ok(mut mouse) = arc_mouse_state.lock() {
+                    mouse.cursor = None;
                 }
             }
             Event::DeviceEvent {
@@ -607,8 +730,8 @@ fn main() {
                 ..
             } => {
                 // Accumulate mouse movement
-                if let Ok(mut position) = arc_mouse_delta.lock() {
-                    *position = (position.0 + delta.0 as f32, position.1 + delta.1 as f32);
+                if let Ok(mut mouse) = arc_mouse_state.lock() {
+                    mouse.delta = (mouse.delta.0 + delta.0 as f32, mouse.delta.1 + delta.1 as f32);
                 }
             }
             _ => {}
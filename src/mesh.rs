@@ -0,0 +1,178 @@
+use crate::gl_object::{Buffer, VertexArray};
+use crate::vertex_layout::{VertexAttribute, VertexLayout, VertexStream};
+use gl::types::{GLenum, GLsizei};
+
+/// CPU-side mesh data in the flattened layout `Mesh::from_data` expects.
+/// Positions are stored homogeneously (x, y, z, w) and colors as RGBA, matching `obj_parser::Parser`.
+pub struct MeshData {
+    pub vertices: Vec<f32>,
+    pub normals: Vec<f32>,
+    pub uvs: Vec<f32>,
+    pub colors: Vec<f32>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    pub fn new() -> MeshData {
+        MeshData {
+            vertices: vec![],
+            normals: vec![],
+            uvs: vec![],
+            colors: vec![],
+            indices: vec![],
+        }
+    }
+
+    /// How many vertices have been added so far.
+    pub fn vertex_count(&self) -> u32 {
+        (self.vertices.len() / 4) as u32
+    }
+
+    /// Append a white vertex and return its index.
+    pub fn push_vertex(&mut self, position: &glm::Vec3, normal: &glm::Vec3, uv: &glm::Vec2) -> u32 {
+        self.push_colored_vertex(position, normal, uv, &glm::vec4(1.0, 1.0, 1.0, 1.0))
+    }
+
+    /// Append a vertex with an explicit color and return its index.
+    pub fn push_colored_vertex(
+        &mut self,
+        position: &glm::Vec3,
+        normal: &glm::Vec3,
+        uv: &glm::Vec2,
+        color: &glm::Vec4,
+    ) -> u32 {
+        let index = self.vertex_count();
+        self.vertices
+            .extend_from_slice(&[position.x, position.y, position.z, 1.0]);
+        self.normals.extend_from_slice(normal.as_slice());
+        self.uvs.extend_from_slice(uv.as_slice());
+        self.colors.extend_from_slice(color.as_slice());
+        index
+    }
+
+    /// Append a counter-clockwise triangle.
+    pub fn push_triangle(&mut self, a: u32, b: u32, c: u32) {
+        self.indices.extend_from_slice(&[a, b, c]);
+    }
+
+    /// Append the vertices and triangles of `other`, like the chunks of a terrain.
+    pub fn append(&mut self, other: &MeshData) {
+        let first = self.vertex_count();
+        self.vertices.extend_from_slice(&other.vertices);
+        self.normals.extend_from_slice(&other.normals);
+        self.uvs.extend_from_slice(&other.uvs);
+        self.colors.extend_from_slice(&other.colors);
+        self.indices
+            .extend(other.indices.iter().map(|&index| first + index));
+    }
+}
+
+impl Default for MeshData {
+    fn default() -> Self {
+        MeshData::new()
+    }
+}
+
+/// How the indices of a mesh are assembled into primitives.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Primitive {
+    Points,
+    Lines,
+    LineStrip,
+    LineLoop,
+    Triangles,
+    TriangleStrip,
+    TriangleFan,
+}
+
+impl From<Primitive> for GLenum {
+    fn from(primitive: Primitive) -> GLenum {
+        match primitive {
+            Primitive::Points => gl::POINTS,
+            Primitive::Lines => gl::LINES,
+            Primitive::LineStrip => gl::LINE_STRIP,
+            Primitive::LineLoop => gl::LINE_LOOP,
+            Primitive::Triangles => gl::TRIANGLES,
+            Primitive::TriangleStrip => gl::TRIANGLE_STRIP,
+            Primitive::TriangleFan => gl::TRIANGLE_FAN,
+        }
+    }
+}
+
+/// The type indices are stored as on the GPU.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexType {
+    U16,
+    U32,
+}
+
+impl From<IndexType> for GLenum {
+    fn from(index_type: IndexType) -> GLenum {
+        match index_type {
+            IndexType::U16 => gl::UNSIGNED_SHORT,
+            IndexType::U32 => gl::UNSIGNED_INT,
+        }
+    }
+}
+
+/// Geometry on the GPU, ready to draw. Owns its vertex array and buffers, so the
+/// CPU-side data can be dropped once the mesh is created.
+pub struct Mesh {
+    vao: VertexArray,
+    index_count: u32,
+    index_type: IndexType,
+    primitive: Primitive,
+}
+
+impl Mesh {
+    /// Upload `indices` for `vao` to draw as `primitive`. The indices are stored as `u16`
+    /// if they all fit. Needs a current GL context.
+    pub unsafe fn new(mut vao: VertexArray, indices: &[u32], primitive: Primitive) -> Mesh {
+        let index_type = if indices.iter().all(|&index| index <= u32::from(u16::MAX)) {
+            let short_indices: Vec<u16> = indices.iter().map(|&index| index as u16).collect();
+            vao.set_element_buffer(Buffer::with_data(&short_indices, gl::STATIC_DRAW));
+            IndexType::U16
+        } else {
+            vao.set_element_buffer(Buffer::with_data(indices, gl::STATIC_DRAW));
+            IndexType::U32
+        };
+        Mesh {
+            vao,
+            index_count: indices.len() as u32,
+            index_type,
+            primitive,
+        }
+    }
+
+    /// Upload `MeshData` as triangles, with the attributes `position`, `normal`, `uv` and `color`
+    /// in separate buffers. Needs a current GL context.
+    pub unsafe fn from_data(data: &MeshData) -> Mesh {
+        let attribute = |name, components| {
+            VertexStream::per_vertex().attribute(VertexAttribute::float(name, components))
+        };
+        let layout = VertexLayout::new()
+            .stream(attribute("position", 4))
+            .stream(attribute("normal", 3))
+            .stream(attribute("uv", 2))
+            .stream(attribute("color", 4));
+        let buffers = [&data.vertices, &data.normals, &data.uvs, &data.colors]
+            .iter()
+            .map(|values| Buffer::with_data(values, gl::STATIC_DRAW))
+            .collect();
+        let vao = layout.vertex_array(buffers);
+        Mesh::new(vao, &data.indices, Primitive::Triangles)
+    }
+
+    /// Draw the whole mesh with the active program.
+    pub fn draw(&self) {
+        self.vao.bind();
+        unsafe {
+            gl::DrawElements(
+                self.primitive.into(),
+                self.index_count as GLsizei,
+                self.index_type.into(),
+                std::ptr::null(),
+            );
+        }
+    }
+}
@@ -0,0 +1,296 @@
+use crate::mesh::Mesh;
+use crate::shader::{Shader, ShaderCache, ShaderKey, Uniform, UniformError};
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
+
+mod file;
+
+pub use file::{check_scene, CameraDescription, Geometry, SceneFile};
+
+/// Refers to a node of a `Scene`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+/// The placement of a node relative to its parent: scaled, then rotated, then translated.
+#[derive(Clone, Copy, Debug)]
+pub struct Transform {
+    pub translation: glm::Vec3,
+    /// Euler angles in radians, applied around x, then y, then z.
+    pub rotation: glm::Vec3,
+    pub scale: glm::Vec3,
+    /// The point the node is rotated and scaled around, in its own coordinates.
+    /// Useful for parts that turn around an axle, like helicopter rotors.
+    pub pivot: glm::Vec3,
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform {
+            translation: glm::zero(),
+            rotation: glm::zero(),
+            scale: glm::vec3(1.0, 1.0, 1.0),
+            pivot: glm::zero(),
+        }
+    }
+
+    /// The matrix from the node's coordinates to its parent's.
+    pub fn matrix(&self) -> glm::Mat4 {
+        let rotation = glm::rotation(self.rotation.z, &glm::vec3(0.0, 0.0, 1.0))
+            * glm::rotation(self.rotation.y, &glm::vec3(0.0, 1.0, 0.0))
+            * glm::rotation(self.rotation.x, &glm::vec3(1.0, 0.0, 0.0));
+        glm::translation(&(self.translation + self.pivot))
+            * rotation
+            * glm::scaling(&self.scale)
+            * glm::translation(&-self.pivot)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+/// How a node's mesh is drawn.
+#[derive(Clone, Debug)]
+pub struct Material {
+    /// The shader variant to draw with.
+    pub shader: ShaderKey,
+    /// Passed to the `material_color` uniform, for shaders that have one.
+    pub color: glm::Vec4,
+}
+
+/// The `material_color` of nodes without a material.
+const DEFAULT_COLOR: glm::Vec4 = glm::Vec4::new(1.0, 1.0, 1.0, 1.0);
+
+pub struct Node {
+    pub name: String,
+    pub transform: Transform,
+    /// Meshes are shared, so the same one can be drawn by several nodes.
+    pub mesh: Option<Rc<Mesh>>,
+    /// What the mesh was made from, so the node can be saved to a scene file.
+    pub source: Option<file::MeshSource>,
+    /// `None` draws with the default shader given to `Scene::draw`.
+    pub material: Option<Material>,
+    /// Hidden nodes are not drawn, and neither are their children.
+    pub visible: bool,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    world: glm::Mat4,
+}
+
+impl Node {
+    /// A node without a mesh, for grouping other nodes.
+    pub fn new(name: &str) -> Node {
+        Node {
+            name: name.to_string(),
+            transform: Transform::identity(),
+            mesh: None,
+            source: None,
+            material: None,
+            visible: true,
+            parent: None,
+            children: vec![],
+            world: glm::identity(),
+        }
+    }
+
+    pub fn transform(mut self, transform: Transform) -> Node {
+        self.transform = transform;
+        self
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+}
+
+/// A hierarchy of nodes, each placed relative to its parent.
+pub struct Scene {
+    nodes: Vec<Node>,
+    roots: Vec<NodeId>,
+    // Uniforms that could not be set are reported once, instead of every frame
+    reported: RefCell<HashSet<String>>,
+}
+
+impl Scene {
+    pub fn new() -> Scene {
+        Scene {
+            nodes: vec![],
+            roots: vec![],
+            reported: RefCell::new(HashSet::new()),
+        }
+    }
+
+    /// Add a node at the top of the hierarchy.
+    pub fn add(&mut self, node: Node) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(node);
+        self.roots.push(id);
+        id
+    }
+
+    /// Move a node, with its children, below another node, or to the top with `None`.
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) {
+        let mut ancestor = parent;
+        while let Some(ancestor_id) = ancestor {
+            assert!(
+                ancestor_id != id,
+                "Node {} cannot be its own ancestor",
+                self[id].name
+            );
+            ancestor = self[ancestor_id].parent;
+        }
+
+        match self[id].parent {
+            Some(old_parent) => self[old_parent].children.retain(|&child| child != id),
+            None => self.roots.retain(|&root| root != id),
+        }
+        match parent {
+            Some(parent) => self[parent].children.push(id),
+            None => self.roots.push(id),
+        }
+        self[id].parent = parent;
+    }
+
+    /// Every node, in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &Node)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| (NodeId(index), node))
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    /// The first node with this name.
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.nodes
+            .iter()
+            .position(|node| node.name == name)
+            .map(NodeId)
+    }
+
+    /// Every shader variant the materials use, to build them before drawing.
+    pub fn shader_keys(&self) -> Vec<ShaderKey> {
+        let mut keys: Vec<ShaderKey> = vec![];
+        for material in self.nodes.iter().filter_map(|node| node.material.as_ref()) {
+            if !keys.contains(&material.shader) {
+                keys.push(material.shader.clone());
+            }
+        }
+        keys
+    }
+
+    /// Recompute the world transform of every node from the local transforms.
+    pub fn update(&mut self) {
+        let mut stack: Vec<(NodeId, glm::Mat4)> = self
+            .roots
+            .iter()
+            .map(|&root| (root, glm::identity()))
+            .collect();
+        while let Some((id, parent_world)) = stack.pop() {
+            let node = &mut self[id];
+            node.world = parent_world * node.transform.matrix();
+            let world = node.world;
+            stack.extend(node.children.iter().map(|&child| (child, world)));
+        }
+    }
+
+    /// Draw every visible node with a mesh, setting the `model` uniform to its world transform.
+    ///
+    /// Nodes are drawn with their material's shader variant, or `default_shader` without one.
+    /// Variants that are not built in `shaders` are skipped, see `shader_keys`, and so are
+    /// nodes whose shader declares `model` or `material_color` with another type.
+    pub unsafe fn draw(&self, shaders: &ShaderCache, default_shader: &ShaderKey) {
+        self.draw_from(&self.roots, shaders, default_shader);
+    }
+
+    /// Draw only `id` and its children, like `draw` does for the whole scene.
+    pub unsafe fn draw_node(&self, id: NodeId, shaders: &ShaderCache, default_shader: &ShaderKey) {
+        self.draw_from(&[id], shaders, default_shader);
+    }
+
+    unsafe fn draw_from(
+        &self,
+        roots: &[NodeId],
+        shaders: &ShaderCache,
+        default_shader: &ShaderKey,
+    ) {
+        let mut stack: Vec<NodeId> = roots.iter().rev().copied().collect();
+        while let Some(id) = stack.pop() {
+            let node = &self[id];
+            if !node.visible {
+                continue;
+            }
+            stack.extend(node.children.iter().rev());
+
+            let mesh = match &node.mesh {
+                Some(mesh) => mesh,
+                None => continue,
+            };
+            let key = node
+                .material
+                .as_ref()
+                .map_or(default_shader, |material| &material.shader);
+            let shader = match shaders.variant(key) {
+                Some(shader) => shader,
+                None => continue,
+            };
+            shader.activate();
+            // Without a material the color is reset, so it is not left over from the last node
+            let color = node
+                .material
+                .as_ref()
+                .map_or(DEFAULT_COLOR, |material| material.color);
+            if self.set_uniform(node, shader, "model", &node.world)
+                && self.set_uniform(node, shader, "material_color", &color)
+            {
+                mesh.draw();
+            }
+        }
+    }
+
+    /// Set a uniform of the shader `node` is drawn with, if the shader has it. Shaders that do
+    /// not read a uniform have it optimized away. Returns false, and prints why the first
+    /// time, if the shader declares it with another type.
+    unsafe fn set_uniform<T: Uniform + ?Sized>(
+        &self,
+        node: &Node,
+        shader: &Shader,
+        name: &str,
+        value: &T,
+    ) -> bool {
+        match shader.try_set(name, value) {
+            Ok(()) | Err(UniformError::Unknown(_)) => true,
+            Err(e) => {
+                let message = format!("Not drawing node {}: {}", node.name, e);
+                if self.reported.borrow_mut().insert(message.clone()) {
+                    eprintln!("{}", message);
+                }
+                false
+            }
+        }
+    }
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Scene::new()
+    }
+}
+
+impl std::ops::Index<NodeId> for Scene {
+    type Output = Node;
+
+    fn index(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+}
+
+impl std::ops::IndexMut<NodeId> for Scene {
+    fn index_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.nodes[id.0]
+    }
+}
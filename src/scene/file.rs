@@ -0,0 +1,874 @@
+use super::{Material, Node, NodeId, Scene, Transform};
+use crate::camera::{Camera, CameraMode, Projection};
+use crate::mesh::{Mesh, MeshData};
+use crate::shader::ShaderKey;
+use crate::surface::{ImplicitSurface, ParametricSurface, SurfaceError};
+use crate::terrain::{TerrainBuilder, TerrainError};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, fs, io,
+    path::Path,
+    rc::Rc,
+};
+
+/// A scene as described in a TOML file: the models and how they are placed, the shaders,
+/// the camera and the lights.
+///
+/// ```toml
+/// [shaders]
+/// vertex = ["./shaders/vertex/simple.vert"]
+/// fragment = ["./shaders/fragment/simple.frag"]
+///
+/// [camera]
+/// position = [0.0, 0.0, 2.0]
+///
+/// [[nodes]]
+/// name = "cube"
+/// model = "./resources/cube.obj"
+/// translation = [0.0, 0.0, -2.0]
+///
+/// [[nodes]]
+/// name = "rotor"
+/// model = "./resources/torus.obj"
+/// parent = "cube"
+/// scale = [0.5, 0.5, 0.5]
+///
+/// [[nodes]]
+/// name = "ground"
+/// terrain = { seed = 7, size = [20.0, 20.0] }
+/// translation = [0.0, -2.0, 0.0]
+///
+/// [[nodes]]
+/// name = "sculpture"
+/// surface = { shape = "gyroid", resolution = 32 }
+/// ```
+///
+/// Everything can be left out, and gets the same default as in code.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SceneFile {
+    pub shaders: ShaderList,
+    pub camera: CameraDescription,
+    pub lights: Vec<LightDescription>,
+    pub nodes: Vec<NodeDescription>,
+}
+
+/// The shader files the viewer switches between. Nodes without a material are drawn
+/// with the current pair, which starts out as the first of each.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShaderList {
+    pub vertex: Vec<String>,
+    pub fragment: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraDescription {
+    pub position: [f32; 3],
+    /// Pitch, yaw and roll in radians.
+    pub rotation: [f32; 3],
+    /// `fly`, `orbit` or `turntable`.
+    pub mode: CameraMode,
+    /// How far in front of the camera the orbit and turntable modes turn around.
+    pub distance: f32,
+    pub projection: Projection,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LightDescription {
+    /// A directional light if the fourth component is 0.
+    pub position: [f32; 4],
+    pub color: [f32; 4],
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NodeDescription {
+    pub name: String,
+    /// The OBJ file to draw. Nodes without a model, terrain or surface only group other nodes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Terrain to generate and draw instead of a model.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub terrain: Option<TerrainDescription>,
+    /// A mathematical surface to generate and draw instead of a model.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub surface: Option<SurfaceDescription>,
+    /// The name of the node this one is placed relative to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    pub translation: [f32; 3],
+    /// Euler angles in radians.
+    pub rotation: [f32; 3],
+    pub scale: [f32; 3],
+    pub pivot: [f32; 3],
+    pub visible: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub material: Option<MaterialDescription>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaterialDescription {
+    /// The stage files of the shader program.
+    pub stages: Vec<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub defines: BTreeMap<String, String>,
+    pub color: [f32; 4],
+}
+
+/// Settings for `TerrainBuilder`, which generates heights from noise unless given a heightmap.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TerrainDescription {
+    /// A grayscale image, black being the lowest point and white the highest.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heightmap: Option<String>,
+    /// The same seed always generates the same noise.
+    pub seed: u64,
+    pub octaves: u32,
+    pub frequency: f32,
+    pub persistence: f32,
+    /// Samples along x and z, at least 2 each. Heightmaps default to their own size.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<[u32; 2]>,
+    /// Width and depth in world units, centered on the node.
+    pub size: [f32; 2],
+    /// Height of the tallest possible point.
+    pub vertical_scale: f32,
+    pub chunk_size: u32,
+}
+
+/// A surface from `surface`, about 2 units across, and how finely to mesh it.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SurfaceDescription {
+    pub shape: SurfaceShape,
+    /// Quads along u and v of the parametric shapes, at least 1 each.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub segments: Option<[u32; 2]>,
+    /// Cells along each axis of the implicit shapes, at least 1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<u32>,
+}
+
+/// The surfaces scene files can use, by their snake_case names like `klein_bottle`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SurfaceShape {
+    // Parametric
+    MobiusStrip,
+    KleinBottle,
+    SineSurface,
+    SineWave,
+    // Implicit
+    #[default]
+    Sphere,
+    Torus,
+    Gyroid,
+}
+
+/// What the mesh of a node is made from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MeshSource {
+    /// An OBJ file.
+    Model(String),
+    Terrain(TerrainDescription),
+    Surface(SurfaceDescription),
+}
+
+/// The geometry of a node, handed to the `load_mesh` of `SceneFile::build` to upload.
+pub enum Geometry<'a> {
+    /// An OBJ file to load.
+    Model(&'a str),
+    /// Generated geometry, like terrain and surfaces.
+    Data(MeshData),
+}
+
+impl Default for CameraDescription {
+    fn default() -> Self {
+        CameraDescription::from_camera(&Camera::new(glm::vec3(0.0, 0.0, 2.0), glm::zero()))
+    }
+}
+
+impl Default for LightDescription {
+    fn default() -> Self {
+        LightDescription {
+            position: [0.0, 0.0, 0.0, 1.0],
+            color: [1.0; 4],
+        }
+    }
+}
+
+impl Default for NodeDescription {
+    fn default() -> Self {
+        NodeDescription::from_node(&Node::new(""), None)
+    }
+}
+
+impl Default for TerrainDescription {
+    fn default() -> Self {
+        TerrainDescription {
+            heightmap: None,
+            seed: 0,
+            octaves: 5,
+            frequency: 4.0,
+            persistence: 0.5,
+            resolution: None,
+            size: [10.0, 10.0],
+            vertical_scale: 1.0,
+            chunk_size: 32,
+        }
+    }
+}
+
+impl Default for MaterialDescription {
+    fn default() -> Self {
+        MaterialDescription {
+            stages: vec![],
+            defines: BTreeMap::new(),
+            color: [1.0; 4],
+        }
+    }
+}
+
+impl CameraDescription {
+    pub fn from_camera(camera: &Camera) -> CameraDescription {
+        CameraDescription {
+            position: camera.position.into(),
+            rotation: camera.rotation.into(),
+            mode: camera.mode,
+            distance: camera.distance,
+            projection: camera.projection,
+        }
+    }
+
+    pub fn camera(&self) -> Camera {
+        Camera {
+            mode: self.mode,
+            distance: self.distance,
+            projection: self.projection,
+            ..Camera::new(self.position.into(), self.rotation.into())
+        }
+    }
+}
+
+impl NodeDescription {
+    fn from_node(node: &Node, parent: Option<String>) -> NodeDescription {
+        let transform = &node.transform;
+        let mut description = NodeDescription {
+            name: node.name.clone(),
+            model: None,
+            terrain: None,
+            surface: None,
+            parent,
+            translation: transform.translation.into(),
+            rotation: transform.rotation.into(),
+            scale: transform.scale.into(),
+            pivot: transform.pivot.into(),
+            visible: node.visible,
+            material: node
+                .material
+                .as_ref()
+                .map(MaterialDescription::from_material),
+        };
+        match &node.source {
+            Some(MeshSource::Model(path)) => description.model = Some(path.clone()),
+            Some(MeshSource::Terrain(terrain)) => description.terrain = Some(terrain.clone()),
+            Some(MeshSource::Surface(surface)) => description.surface = Some(surface.clone()),
+            None => {}
+        }
+        description
+    }
+
+    /// What the node's mesh is made from, failing if it is given more than one source.
+    pub fn source(&self) -> Result<Option<MeshSource>, SceneFileError> {
+        let mut sources: Vec<MeshSource> = vec![
+            self.model.clone().map(MeshSource::Model),
+            self.terrain.clone().map(MeshSource::Terrain),
+            self.surface.clone().map(MeshSource::Surface),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if sources.len() > 1 {
+            return Err(SceneFileError::SeveralMeshes {
+                node: self.name.clone(),
+            });
+        }
+        Ok(sources.pop())
+    }
+
+    fn transform(&self) -> Transform {
+        Transform {
+            translation: self.translation.into(),
+            rotation: self.rotation.into(),
+            scale: self.scale.into(),
+            pivot: self.pivot.into(),
+        }
+    }
+}
+
+impl TerrainDescription {
+    /// Generate the terrain, as one mesh.
+    pub fn mesh(&self) -> Result<MeshData, SceneFileError> {
+        let builder = match &self.heightmap {
+            Some(path) => {
+                TerrainBuilder::from_image(path).map_err(|error| SceneFileError::Heightmap {
+                    path: path.clone(),
+                    error,
+                })?
+            }
+            None => TerrainBuilder::from_noise(self.seed).octaves(
+                self.octaves,
+                self.frequency,
+                self.persistence,
+            ),
+        };
+        let mut builder = builder
+            .size(self.size[0], self.size[1])
+            .vertical_scale(self.vertical_scale)
+            .chunk_size(self.chunk_size);
+        if let Some([x_samples, z_samples]) = self.resolution {
+            builder = builder.resolution(x_samples, z_samples);
+        }
+        let terrain = builder.build().map_err(SceneFileError::Terrain)?;
+        Ok(terrain.mesh())
+    }
+}
+
+impl SurfaceDescription {
+    /// Mesh the surface.
+    pub fn mesh(&self) -> Result<MeshData, SceneFileError> {
+        match self.shape {
+            SurfaceShape::MobiusStrip => self.parametric(ParametricSurface::mobius_strip(0.4)),
+            SurfaceShape::KleinBottle => self.parametric(ParametricSurface::klein_bottle(2.0)),
+            SurfaceShape::SineSurface => self.parametric(ParametricSurface::sine_surface()),
+            SurfaceShape::SineWave => self.parametric(ParametricSurface::sine_wave(2.0, 0.2, 6.0)),
+            SurfaceShape::Sphere => self.implicit(ImplicitSurface::sphere(1.0)),
+            SurfaceShape::Torus => self.implicit(ImplicitSurface::torus(0.8, 0.3)),
+            SurfaceShape::Gyroid => self.implicit(ImplicitSurface::gyroid(2.0, 1.0)),
+        }
+    }
+
+    fn parametric(&self, surface: ParametricSurface) -> Result<MeshData, SceneFileError> {
+        let surface = match self.segments {
+            Some([u_segments, v_segments]) => surface.segments(u_segments, v_segments),
+            None => surface,
+        };
+        surface.build().map_err(SceneFileError::Surface)
+    }
+
+    fn implicit(&self, surface: ImplicitSurface) -> Result<MeshData, SceneFileError> {
+        let surface = match self.resolution {
+            Some(cells) => surface.resolution(cells),
+            None => surface,
+        };
+        surface.build().map_err(SceneFileError::Surface)
+    }
+}
+
+impl MeshSource {
+    /// Generate the geometry, or point to the file to load it from.
+    pub fn geometry(&self) -> Result<Geometry<'_>, SceneFileError> {
+        match self {
+            MeshSource::Model(path) => Ok(Geometry::Model(path)),
+            MeshSource::Terrain(terrain) => terrain.mesh().map(Geometry::Data),
+            MeshSource::Surface(surface) => surface.mesh().map(Geometry::Data),
+        }
+    }
+}
+
+impl MaterialDescription {
+    fn from_material(material: &Material) -> MaterialDescription {
+        MaterialDescription {
+            stages: material.shader.stages().to_vec(),
+            defines: material.shader.defines().clone(),
+            color: material.color.into(),
+        }
+    }
+
+    fn material(&self) -> Material {
+        let stages: Vec<&str> = self.stages.iter().map(String::as_str).collect();
+        let shader = self
+            .defines
+            .iter()
+            .fold(ShaderKey::new(&stages), |key, (name, value)| {
+                key.define(name, value)
+            });
+        Material {
+            shader,
+            color: self.color.into(),
+        }
+    }
+}
+
+impl SceneFile {
+    /// A scene with only the model in `path`, at the origin and lit from above, drawn with
+    /// the simplest shaders.
+    pub fn from_model(path: &str) -> SceneFile {
+        let name = Path::new(path).file_stem().map_or_else(
+            || path.to_string(),
+            |stem| stem.to_string_lossy().into_owned(),
+        );
+        SceneFile {
+            shaders: ShaderList {
+                vertex: vec!["./shaders/vertex/simple.vert".to_string()],
+                fragment: vec!["./shaders/fragment/simple.frag".to_string()],
+            },
+            camera: CameraDescription::default(),
+            lights: vec![LightDescription {
+                position: [0.0, 5.0, 5.0, 1.0],
+                ..LightDescription::default()
+            }],
+            nodes: vec![NodeDescription {
+                name,
+                model: Some(path.to_string()),
+                ..NodeDescription::default()
+            }],
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<SceneFile, SceneFileError> {
+        let path = path.as_ref().display().to_string();
+        let text = fs::read_to_string(&path).map_err(|error| SceneFileError::Read {
+            path: path.clone(),
+            error,
+        })?;
+        let mut scene_file: SceneFile =
+            toml::from_str(&text).map_err(|error| SceneFileError::Parse {
+                path: path.clone(),
+                error,
+            })?;
+        let projection = &mut scene_file.camera.projection;
+        if !projection.planes_valid() {
+            return Err(SceneFileError::ClipPlanes {
+                path,
+                near: projection.near,
+                far: projection.far,
+            });
+        }
+        // Keep the field of view in the range the keys can change it in
+        projection.set_fov(projection.fov_y);
+        Ok(scene_file)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SceneFileError> {
+        let text = toml::to_string(self).map_err(SceneFileError::Serialize)?;
+        fs::write(&path, text).map_err(|error| SceneFileError::Write {
+            path: path.as_ref().display().to_string(),
+            error,
+        })
+    }
+
+    /// Build the scene graph. `load_mesh` is called once for every model file and generated
+    /// mesh, and nodes with the same source share the mesh.
+    pub fn build<F>(&self, mut load_mesh: F) -> Result<Scene, SceneFileError>
+    where
+        F: FnMut(Geometry) -> Rc<Mesh>,
+    {
+        // Nodes are found by name, so the names and parents have to be right before building
+        self.check_parents()?;
+        let mut scene = Scene::new();
+        let mut meshes: Vec<(MeshSource, Rc<Mesh>)> = vec![];
+        let mut ids: HashMap<&str, NodeId> = HashMap::new();
+        for description in &self.nodes {
+            let mut node = Node::new(&description.name).transform(description.transform());
+            node.visible = description.visible;
+            node.material = description.material.as_ref().map(|m| m.material());
+            if let Some(source) = description.source()? {
+                let loaded = meshes.iter().find(|(loaded, _)| *loaded == source);
+                let mesh = match loaded {
+                    Some((_, mesh)) => mesh.clone(),
+                    None => {
+                        let mesh = load_mesh(source.geometry()?);
+                        meshes.push((source.clone(), mesh.clone()));
+                        mesh
+                    }
+                };
+                node.mesh = Some(mesh);
+                node.source = Some(source);
+            }
+            ids.insert(&description.name, scene.add(node));
+        }
+
+        // Parents are resolved afterwards, so they can be listed after their children
+        for description in &self.nodes {
+            if let Some(parent) = &description.parent {
+                let id = ids[description.name.as_str()];
+                scene.set_parent(id, Some(ids[parent.as_str()]));
+            }
+        }
+        scene.update();
+        Ok(scene)
+    }
+
+    /// Check that no two nodes have the same name, that every parent is the name of a node,
+    /// and that no node is its own ancestor.
+    pub fn check_parents(&self) -> Result<(), SceneFileError> {
+        for (index, description) in self.nodes.iter().enumerate() {
+            if self.nodes[..index]
+                .iter()
+                .any(|node| node.name == description.name)
+            {
+                return Err(SceneFileError::DuplicateName {
+                    node: description.name.clone(),
+                });
+            }
+        }
+        for description in &self.nodes {
+            let parent = match &description.parent {
+                Some(parent) => parent,
+                None => continue,
+            };
+            if !self.nodes.iter().any(|node| &node.name == parent) {
+                return Err(SceneFileError::UnknownParent {
+                    node: description.name.clone(),
+                    parent: parent.clone(),
+                });
+            }
+            if *parent == description.name || self.is_ancestor(&description.name, parent) {
+                return Err(SceneFileError::ParentCycle {
+                    node: description.name.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace the nodes with the ones in `scene`, to save changes made while running.
+    /// Nodes whose mesh did not come from a scene file are saved without it.
+    pub fn update_nodes(&mut self, scene: &Scene) {
+        self.nodes = scene
+            .iter()
+            .map(|(_, node)| {
+                let parent = node.parent().map(|parent| scene[parent].name.clone());
+                NodeDescription::from_node(node, parent)
+            })
+            .collect();
+    }
+
+    /// Whether following the parents of `ancestor` leads to `node`.
+    fn is_ancestor(&self, node: &str, ancestor: &str) -> bool {
+        let mut current = ancestor;
+        // Any longer chain has to contain a cycle
+        for _ in 0..self.nodes.len() {
+            let parent = self
+                .nodes
+                .iter()
+                .find(|description| description.name == current)
+                .and_then(|description| description.parent.as_deref());
+            match parent {
+                Some(parent) if parent == node => return true,
+                Some(parent) => current = parent,
+                None => return false,
+            }
+        }
+        true
+    }
+}
+
+/// Load the scene file at `path` and look for problems without opening a window, printing
+/// the ones found: names used by several nodes, parents that do not resolve, nodes with more
+/// than one mesh, and model, heightmap and shader files that do not exist.
+/// Returns false if anything is wrong.
+pub fn check_scene(path: &str) -> bool {
+    let scene_file = match SceneFile::load(path) {
+        Ok(scene_file) => scene_file,
+        Err(e) => {
+            eprintln!("{}", e);
+            return false;
+        }
+    };
+
+    let mut errors = 0;
+    if scene_file.shaders.vertex.is_empty() || scene_file.shaders.fragment.is_empty() {
+        eprintln!("The scene needs at least one vertex and one fragment shader");
+        errors += 1;
+    }
+    if let Err(e) = scene_file.check_parents() {
+        eprintln!("{}", e);
+        errors += 1;
+    }
+    for node in &scene_file.nodes {
+        if let Err(e) = node.source() {
+            eprintln!("{}", e);
+            errors += 1;
+        }
+    }
+
+    let shaders = scene_file
+        .shaders
+        .vertex
+        .iter()
+        .chain(&scene_file.shaders.fragment);
+    let models = scene_file
+        .nodes
+        .iter()
+        .filter_map(|node| node.model.as_ref());
+    let heightmaps = scene_file
+        .nodes
+        .iter()
+        .filter_map(|node| node.terrain.as_ref()?.heightmap.as_ref());
+    let stages = scene_file
+        .nodes
+        .iter()
+        .filter_map(|node| node.material.as_ref())
+        .flat_map(|material| &material.stages);
+    for file in shaders.chain(models).chain(heightmaps).chain(stages) {
+        if !Path::new(file).is_file() {
+            eprintln!("{} does not exist", file);
+            errors += 1;
+        }
+    }
+
+    println!(
+        "Checked {} with {} nodes, found {} errors",
+        path,
+        scene_file.nodes.len(),
+        errors
+    );
+    errors == 0
+}
+
+/// Everything that can go wrong while loading or saving a scene file.
+#[derive(Debug)]
+pub enum SceneFileError {
+    Read {
+        path: String,
+        error: io::Error,
+    },
+    Parse {
+        path: String,
+        error: toml::de::Error,
+    },
+    Write {
+        path: String,
+        error: io::Error,
+    },
+    Serialize(toml::ser::Error),
+    /// The camera does not have `0 < near < far`.
+    ClipPlanes {
+        path: String,
+        near: f32,
+        far: f32,
+    },
+    /// Two nodes have the same name, so a parent cannot refer to either of them.
+    DuplicateName {
+        node: String,
+    },
+    /// A node names a parent that is not in the file.
+    UnknownParent {
+        node: String,
+        parent: String,
+    },
+    /// A node ends up being its own ancestor.
+    ParentCycle {
+        node: String,
+    },
+    /// A node has more than one of a model, a terrain and a surface.
+    SeveralMeshes {
+        node: String,
+    },
+    Heightmap {
+        path: String,
+        error: image::ImageError,
+    },
+    Terrain(TerrainError),
+    Surface(SurfaceError),
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneFileError::Read { path, error } => {
+                write!(f, "Failed to read scene file {}: {}", path, error)
+            }
+            SceneFileError::Parse { path, error } => {
+                write!(f, "Failed to parse scene file {}: {}", path, error)
+            }
+            SceneFileError::Write { path, error } => {
+                write!(f, "Failed to write scene file {}: {}", path, error)
+            }
+            SceneFileError::Serialize(error) => write!(f, "Failed to save the scene: {}", error),
+            SceneFileError::ClipPlanes { path, near, far } => write!(
+                f,
+                "Invalid camera in {}: expected 0 < near < far, but near is {} and far is {}",
+                path, near, far
+            ),
+            SceneFileError::DuplicateName { node } => {
+                write!(f, "There is more than one node named {}", node)
+            }
+            SceneFileError::UnknownParent { node, parent } => write!(
+                f,
+                "Node {} has parent {}, but there is no node with that name",
+                node, parent
+            ),
+            SceneFileError::ParentCycle { node } => {
+                write!(f, "Node {} is its own ancestor", node)
+            }
+            SceneFileError::SeveralMeshes { node } => {
+                write!(
+                    f,
+                    "Node {} has more than one of model, terrain and surface",
+                    node
+                )
+            }
+            SceneFileError::Heightmap { path, error } => {
+                write!(f, "Failed to load heightmap {}: {}", path, error)
+            }
+            SceneFileError::Terrain(error) => write!(f, "Failed to build terrain: {}", error),
+            SceneFileError::Surface(error) => write!(f, "Failed to build surface: {}", error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> SceneFile {
+        toml::from_str(text).unwrap()
+    }
+
+    /// Load a scene file from a temporary file with `text` in it.
+    fn load(name: &str, text: &str) -> Result<SceneFile, SceneFileError> {
+        let path = std::env::temp_dir().join(format!("gloom-rs-scene-{}.toml", name));
+        fs::write(&path, text).unwrap();
+        let scene_file = SceneFile::load(&path);
+        fs::remove_file(&path).unwrap();
+        scene_file
+    }
+
+    #[test]
+    fn parents_can_come_after_their_children() {
+        let scene_file = parse(
+            r#"
+            [[nodes]]
+            name = "rotor"
+            parent = "body"
+
+            [[nodes]]
+            name = "body"
+            "#,
+        );
+        assert!(scene_file.check_parents().is_ok());
+    }
+
+    #[test]
+    fn duplicate_names() {
+        let scene_file = parse("[[nodes]]\nname = \"a\"\n\n[[nodes]]\nname = \"a\"\n");
+        match scene_file.check_parents() {
+            Err(SceneFileError::DuplicateName { node }) => assert_eq!(node, "a"),
+            other => panic!("Expected a duplicate name, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unknown_parent() {
+        let scene_file = parse("[[nodes]]\nname = \"a\"\nparent = \"b\"\n");
+        match scene_file.check_parents() {
+            Err(SceneFileError::UnknownParent { node, parent }) => {
+                assert_eq!((node.as_str(), parent.as_str()), ("a", "b"))
+            }
+            other => panic!("Expected an unknown parent, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parent_cycles() {
+        let own_parent = parse("[[nodes]]\nname = \"a\"\nparent = \"a\"\n");
+        assert!(matches!(
+            own_parent.check_parents(),
+            Err(SceneFileError::ParentCycle { .. })
+        ));
+
+        let cycle = parse(
+            r#"
+            [[nodes]]
+            name = "a"
+            parent = "c"
+
+            [[nodes]]
+            name = "b"
+            parent = "a"
+
+            [[nodes]]
+            name = "c"
+            parent = "b"
+            "#,
+        );
+        assert!(matches!(
+            cycle.check_parents(),
+            Err(SceneFileError::ParentCycle { .. })
+        ));
+    }
+
+    #[test]
+    fn one_mesh_per_node() {
+        let scene_file = parse(
+            r#"
+            [[nodes]]
+            name = "both"
+            model = "./resources/cube.obj"
+            surface = { shape = "sphere" }
+
+            [[nodes]]
+            name = "model"
+            model = "./resources/cube.obj"
+
+            [[nodes]]
+            name = "group"
+            "#,
+        );
+        assert!(matches!(
+            scene_file.nodes[0].source(),
+            Err(SceneFileError::SeveralMeshes { .. })
+        ));
+        assert!(matches!(
+            scene_file.nodes[1].source(),
+            Ok(Some(MeshSource::Model(_)))
+        ));
+        assert!(matches!(scene_file.nodes[2].source(), Ok(None)));
+    }
+
+    #[test]
+    fn load_checks_the_clip_planes() {
+        let text = "[camera.projection]\nnear = 10.0\nfar = 1.0\n";
+        match load("clip-planes", text) {
+            Err(SceneFileError::ClipPlanes { near, far, .. }) => {
+                assert_eq!((near, far), (10.0, 1.0))
+            }
+            other => panic!("Expected invalid clip planes, got {:?}", other),
+        }
+        assert!(load(
+            "valid-clip-planes",
+            "[camera.projection]\nnear = 0.5\nfar = 50.0\n"
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn saved_files_load_the_same() {
+        let scene_file = SceneFile::from_model("./resources/cube.obj");
+        let text = toml::to_string(&scene_file).unwrap();
+        let loaded = load("round-trip", &text).unwrap();
+        assert_eq!(loaded.nodes.len(), 1);
+        assert_eq!(loaded.nodes[0].name, "cube");
+        assert_eq!(loaded.nodes[0].model, scene_file.nodes[0].model);
+        assert_eq!(loaded.shaders.vertex, scene_file.shaders.vertex);
+    }
+
+    #[test]
+    fn default_scene_is_valid() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/scenes/default.toml");
+        let scene_file = SceneFile::load(path).unwrap();
+        assert!(scene_file.check_parents().is_ok());
+        for node in &scene_file.nodes {
+            assert!(node.source().is_ok(), "{} has several meshes", node.name);
+        }
+    }
+}
@@ -1,38 +1,198 @@
+use crate::gl_object::Program;
+use crate::vertex_layout;
 use gl;
 use std::{
     ptr,
     str,
+    cell::RefCell,
+    collections::HashMap,
     ffi::CString,
-    path::Path,
+    path::{Path, PathBuf},
 };
 
+mod binary_cache;
+mod cache;
+mod check;
+mod diagnostic;
+mod error;
+mod preprocessor;
+mod reflection;
+mod spirv;
+mod std140;
+mod uniform;
+mod watcher;
+
+pub use binary_cache::ProgramBinaryCache;
+pub use cache::{ShaderCache, ShaderKey};
+pub use check::check_shaders;
+pub use diagnostic::{Diagnostic, Severity};
+pub use error::ShaderError;
+pub use preprocessor::{PreprocessedSource, Preprocessor};
+pub use reflection::{Attribute, ProgramInterface};
+pub use spirv::{load_with as load_spirv_with, Specialization};
+pub(crate) use std140::uniform_block;
+pub use std140::{Std140, Std140Buffer, UniformBlock, UniformBuffer};
+pub use uniform::{Uniform, UniformError, UniformInfo, UniformValue};
+pub use watcher::ShaderWatcher;
+
 pub struct Shader {
-    pub program_id: u32,
+    program: Program,
+    interface: ProgramInterface,
+    uniforms: HashMap<String, UniformInfo>,
+    // Locations of individual array elements like `lights[2]`, looked up on first use
+    element_uniforms: RefCell<HashMap<String, UniformInfo>>,
+    sources: Vec<PathBuf>,
 }
 
 pub struct ShaderBuilder {
-    program_id: u32,
+    // Taken by the shader once linked, and deleted with the builder otherwise
+    program: Option<Program>,
     shaders: Vec::<u32>,
+    // The attached stage files
+    paths: Vec<String>,
+    // Every file that went into the program, includes too
+    sources: Vec<PathBuf>,
+    preprocessor: Preprocessor,
+    // Stages are compiled when linking, so a cached binary can skip compilation entirely
+    stages: Vec<Stage>,
+    binary_cache: Option<ProgramBinaryCache>,
+}
+
+struct Stage {
+    shader_type: ShaderType,
+    source: StageSource,
+    path: String,
+}
+
+enum StageSource {
+    Glsl(PreprocessedSource),
+    Spirv { binary: Vec<u8>, specialization: Specialization },
+}
+
+impl Stage {
+    /// Everything that affects the compiled stage, for the binary cache key.
+    fn fingerprint(&self) -> Vec<u8> {
+        match &self.source {
+            StageSource::Glsl(source) => source.code.as_bytes().to_vec(),
+            StageSource::Spirv { binary, specialization } => {
+                let mut bytes = binary.clone();
+                bytes.extend_from_slice(&specialization.fingerprint());
+                bytes
+            }
+        }
+    }
 }
 
 #[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum ShaderType {
     Vertex,
     Fragment,
     TessellationControl,
     TessellationEvaluation,
     Geometry,
+    Compute,
 }
 
 impl Shader {
     // Make sure the shader is active before calling this
     pub unsafe fn get_uniform_location(&self, name: &str) -> i32 {
         let name_cstr = CString::new(name).expect("CString::new failed");
-        gl::GetUniformLocation(self.program_id, name_cstr.as_ptr())
+        gl::GetUniformLocation(self.program_id(), name_cstr.as_ptr())
     }
 
     pub unsafe fn activate(&self) {
-        gl::UseProgram(self.program_id);
+        gl::UseProgram(self.program_id());
+    }
+
+    pub fn program_id(&self) -> u32 {
+        self.program.id()
+    }
+
+    /// The files this program was built from, for watching them.
+    pub fn sources(&self) -> &[PathBuf] {
+        &self.sources
+    }
+
+    /// Type, size and location of an active uniform. Array elements like `lights[2]` are accepted too.
+    pub fn uniform(&self, name: &str) -> Option<UniformInfo> {
+        if let Some(info) = self.uniforms.get(name) {
+            return Some(*info);
+        }
+        if let Some(info) = self.element_uniforms.borrow().get(name) {
+            return Some(*info);
+        }
+
+        // `name[index]` shares the type of the array, but has its own location
+        let (array, index) = name.strip_suffix(']')?.rsplit_once('[')?;
+        let index = index.parse::<i32>().ok()?;
+        let array_info = self.uniforms.get(array)?;
+        if index < 0 || index >= array_info.size {
+            return None;
+        }
+        let location = unsafe { self.get_uniform_location(name) };
+        if location < 0 {
+            return None;
+        }
+        let info = UniformInfo {
+            location,
+            gl_type: array_info.gl_type,
+            size: array_info.size - index,
+        };
+        self.element_uniforms.borrow_mut().insert(name.to_string(), info);
+        Some(info)
+    }
+
+    /// Set a uniform by name, checking the value against the declared GLSL type.
+    /// The program does not need to be active.
+    pub unsafe fn try_set<T: Uniform + ?Sized>(&self, name: &str, value: &T) -> Result<(), UniformError> {
+        let info = self
+            .uniform(name)
+            .ok_or_else(|| UniformError::Unknown(name.to_string()))?;
+        if !value.accepts(info.gl_type) {
+            return Err(UniformError::TypeMismatch {
+                name: name.to_string(),
+                expected: uniform::glsl_type_name(info.gl_type),
+                given: value.glsl_type(),
+            });
+        }
+        if value.element_count() > info.size as usize {
+            return Err(UniformError::TooManyElements {
+                name: name.to_string(),
+                size: info.size,
+                given: value.element_count(),
+            });
+        }
+        value.upload(self.program_id(), info.location);
+        Ok(())
+    }
+
+    /// Everything the linked program exposes: attributes, uniforms and blocks.
+    pub fn interface(&self) -> &ProgramInterface {
+        &self.interface
+    }
+
+    pub fn attributes(&self) -> &[Attribute] {
+        &self.interface.attributes
+    }
+
+    /// Build the location cache from the reflected uniforms. Uniforms inside blocks have no location.
+    fn uniform_cache(interface: &ProgramInterface) -> HashMap<String, UniformInfo> {
+        interface
+            .uniforms
+            .iter()
+            .filter_map(|uniform| {
+                let location = uniform.location?;
+                // Arrays are reported as `name[0]`, but are set through their plain name
+                let name = uniform.name.strip_suffix("[0]").unwrap_or(&uniform.name);
+                let info = UniformInfo {
+                    location,
+                    gl_type: uniform.gl_type,
+                    size: uniform.size,
+                };
+                Some((name.to_string(), info))
+            })
+            .collect()
     }
 }
 
@@ -44,112 +204,299 @@ impl Into<gl::types::GLenum> for ShaderType {
             ShaderType::TessellationControl     => { gl::TESS_CONTROL_SHADER    },
             ShaderType::TessellationEvaluation  => { gl::TESS_EVALUATION_SHADER } ,
             ShaderType::Geometry                => { gl::GEOMETRY_SHADER        },
+            ShaderType::Compute                 => { gl::COMPUTE_SHADER         },
         }
     }
 }
 
 impl ShaderType {
-    fn from_ext(ext: &std::ffi::OsStr) -> Result<ShaderType, String> {
-        match ext.to_str().expect("Failed to read extension") {
-            "vert" => { Ok(ShaderType::Vertex) },
-            "frag" => { Ok(ShaderType::Fragment) },
-            "tcs"  => { Ok(ShaderType::TessellationControl) },
-            "tes"  => { Ok(ShaderType::TessellationEvaluation) },
-            "geom" => { Ok(ShaderType::Geometry) },
-            e => { Err(e.to_string()) },
+    /// The stage of a shader file, from its extension like `.vert` or `.frag`.
+    pub fn from_path(path: &Path) -> Result<ShaderType, ShaderError> {
+        match path.extension().and_then(std::ffi::OsStr::to_str) {
+            Some("vert") => { Ok(ShaderType::Vertex) },
+            Some("frag") => { Ok(ShaderType::Fragment) },
+            Some("tcs")  => { Ok(ShaderType::TessellationControl) },
+            Some("tes")  => { Ok(ShaderType::TessellationEvaluation) },
+            Some("geom") => { Ok(ShaderType::Geometry) },
+            Some("comp") => { Ok(ShaderType::Compute) },
+            _ => { Err(ShaderError::UnknownExtension { path: path.display().to_string() }) },
         }
     }
 }
 
+impl std::fmt::Display for ShaderType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            ShaderType::Vertex                  => { "vertex"                  },
+            ShaderType::Fragment                => { "fragment"                },
+            ShaderType::TessellationControl     => { "tessellation control"    },
+            ShaderType::TessellationEvaluation  => { "tessellation evaluation" },
+            ShaderType::Geometry                => { "geometry"                },
+            ShaderType::Compute                 => { "compute"                 },
+        };
+        write!(f, "{}", name)
+    }
+}
+
 impl ShaderBuilder {
     pub unsafe fn new() -> ShaderBuilder {
         ShaderBuilder {
-            program_id: gl::CreateProgram(),
+            program: Some(Program::new()),
             shaders: vec![],
+            paths: vec![],
+            sources: vec![],
+            preprocessor: Preprocessor::new(),
+            stages: vec![],
+            binary_cache: None,
         }
     }
 
-    pub unsafe fn attach_file(self, shader_path: &str) -> ShaderBuilder {
-        //println!("{shader_path}");
+    /// Add a directory to search for `#include`d files. Only affects stages attached afterwards.
+    pub fn include_path<P: AsRef<Path>>(mut self, path: P) -> ShaderBuilder {
+        self.preprocessor.include_path(path);
+        self
+    }
+
+    /// Inject `#define name value` after the `#version` line. Only affects stages attached afterwards.
+    pub fn define(mut self, name: &str, value: &str) -> ShaderBuilder {
+        self.preprocessor.define(name, value);
+        self
+    }
+
+    /// Load the linked program from, and save it to, a binary cache in `directory` if the driver supports it.
+    pub fn binary_cache<P: AsRef<Path>>(mut self, directory: P) -> ShaderBuilder {
+        self.binary_cache = Some(ProgramBinaryCache::new(directory));
+        self
+    }
+
+    /// Attach GLSL source, or a SPIR-V module if the file ends in `.spv`, like `simple.frag.spv`.
+    pub unsafe fn attach_file(mut self, shader_path: &str) -> Result<ShaderBuilder, ShaderError> {
+        let path = Path::new(shader_path);
+        if path.extension().is_some_and(|extension| extension == "spv") {
+            return self.attach_spirv(shader_path, Specialization::new());
+        }
+        let shader_type = ShaderType::from_path(path)?;
+        self.check_not_mixed(false, shader_path)?;
+        self.paths.push(shader_path.to_string());
+        let source = self.preprocessor.process_file(path)?;
+        self.add_sources(&source.files);
+        self.stages.push(Stage { shader_type, source: StageSource::Glsl(source), path: shader_path.to_string() });
+        Ok(self)
+    }
+
+    /// Attach a SPIR-V module through `ARB_gl_spirv`. The stage is taken from the extension before
+    /// `.spv`, so `simple.frag.spv` is a fragment shader. Needs `spirv::load_with` to have been called.
+    /// A program cannot mix SPIR-V and GLSL stages.
+    pub unsafe fn attach_spirv(mut self, shader_path: &str, specialization: Specialization) -> Result<ShaderBuilder, ShaderError> {
         let path = Path::new(shader_path);
-        if let Some(extension) = path.extension() {
-            let shader_type = ShaderType::from_ext(extension)
-                .expect("Failed to parse file extension.");
-            let shader_src = std::fs::read_to_string(path)
-                .expect(&format!("Failed to read shader source. {}", shader_path));
-            self.compile_shader(&shader_src, shader_type)
-        } else {
-            panic!("Failed to read extension of file with path: {}", shader_path);
+        let error = |message: &str| ShaderError::Spirv { path: shader_path.to_string(), message: message.to_string() };
+        let shader_type = ShaderType::from_path(&path.with_extension(""))
+            .map_err(|_| ShaderError::UnknownExtension { path: shader_path.to_string() })?;
+        if !spirv::supported() {
+            return Err(error("the driver does not support ARB_gl_spirv"));
         }
+        self.check_not_mixed(true, shader_path)?;
+        let binary = std::fs::read(path).map_err(|error| ShaderError::Read { path: shader_path.to_string(), error })?;
+        spirv::validate(&binary).map_err(error)?;
+
+        self.paths.push(shader_path.to_string());
+        self.add_sources(&[path.to_path_buf()]);
+        self.stages.push(Stage { shader_type, source: StageSource::Spirv { binary, specialization }, path: shader_path.to_string() });
+        Ok(self)
     }
 
-    pub unsafe fn compile_shader(mut self, shader_src: &str, shader_type: ShaderType) -> ShaderBuilder {
-        let shader = gl::CreateShader(shader_type.into());
-        let c_str_shader = CString::new(shader_src.as_bytes()).unwrap();
-        gl::ShaderSource(shader, 1, &c_str_shader.as_ptr(), ptr::null());
-        gl::CompileShader(shader);
+    /// A program cannot mix SPIR-V and GLSL stages, so only attach a stage of the kind already attached.
+    fn check_not_mixed(&self, spirv: bool, path: &str) -> Result<(), ShaderError> {
+        let mixed = self.stages.iter().any(|stage| matches!(stage.source, StageSource::Spirv { .. }) != spirv);
+        if mixed {
+            return Err(ShaderError::MixedStages { path: path.to_string() });
+        }
+        Ok(())
+    }
 
-        if !self.check_shader_errors(shader) {
-            panic!("Shader failed to compile.");
+    fn program_id(&self) -> u32 {
+        self.program.as_ref().map_or(0, Program::id)
+    }
+
+    fn add_sources(&mut self, files: &[PathBuf]) {
+        for file in files {
+            if !self.sources.contains(file) {
+                self.sources.push(file.clone());
+            }
         }
+    }
 
+    unsafe fn compile(&mut self, stage: &Stage) -> Result<(), ShaderError> {
+        let Stage { shader_type, source, path } = stage;
+        let shader = gl::CreateShader((*shader_type).into());
+        // Keep track of the shader right away, so it is deleted if anything fails
         self.shaders.push(shader);
 
-        self
+        match source {
+            StageSource::Glsl(source) => {
+                let c_str_shader = CString::new(source.code.as_bytes()).unwrap();
+                gl::ShaderSource(shader, 1, &c_str_shader.as_ptr(), ptr::null());
+                gl::CompileShader(shader);
+
+                if let Some(log) = self.check_shader_errors(shader) {
+                    return Err(ShaderError::Compile {
+                        path: path.clone(),
+                        stage: *shader_type,
+                        diagnostics: Diagnostic::parse_log(&log, source),
+                        log,
+                        files: source.files.iter().map(|f| f.display().to_string()).collect(),
+                    });
+                }
+            }
+            StageSource::Spirv { binary, specialization } => {
+                // Specializing takes the place of compiling, and fails on unknown entry points or constants
+                spirv::load(shader, binary, specialization);
+                if let Some(log) = self.check_shader_errors(shader) {
+                    return Err(ShaderError::Compile {
+                        path: path.clone(),
+                        stage: *shader_type,
+                        log,
+                        files: vec![path.clone()],
+                        diagnostics: vec![],
+                    });
+                }
+            }
+        }
+
+        Ok(())
     }
 
-    unsafe fn check_shader_errors(&self, shader_id: u32) -> bool {
+    /// Returns the full info log if compilation failed.
+    unsafe fn check_shader_errors(&self, shader_id: u32) -> Option<String> {
         let mut success = i32::from(gl::FALSE);
-        let mut info_log = Vec::with_capacity(512);
-        info_log.set_len(512 - 1);
         gl::GetShaderiv(shader_id, gl::COMPILE_STATUS, &mut success);
-        if success != i32::from(gl::TRUE) {
-            gl::GetShaderInfoLog(
-                shader_id,
-                512,
-                ptr::null_mut(),
-                info_log.as_mut_ptr() as *mut gl::types::GLchar,
-            );
-            println!("ERROR::Shader Compilation Failed!\n{}", String::from_utf8_lossy(&info_log));
-            return false;
+        if success == i32::from(gl::TRUE) {
+            return None;
         }
-        true
+
+        let mut log_length = 0;
+        gl::GetShaderiv(shader_id, gl::INFO_LOG_LENGTH, &mut log_length);
+        let mut info_log = vec![0u8; log_length.max(1) as usize];
+        let mut written = 0;
+        gl::GetShaderInfoLog(
+            shader_id,
+            log_length,
+            &mut written,
+            info_log.as_mut_ptr() as *mut gl::types::GLchar,
+        );
+        info_log.truncate(written as usize);
+        Some(String::from_utf8_lossy(&info_log).to_string())
     }
 
-    unsafe fn check_linker_errors(&self) -> bool {
+    /// Returns the full info log if linking failed.
+    unsafe fn check_linker_errors(&self) -> Option<String> {
         let mut success = i32::from(gl::FALSE);
-        let mut info_log = Vec::with_capacity(512);
-        info_log.set_len(512 - 1);
-        gl::GetProgramiv(self.program_id, gl::LINK_STATUS, &mut success);
-        if success != i32::from(gl::TRUE) {
-            gl::GetProgramInfoLog(
-                self.program_id,
-                512,
-                ptr::null_mut(),
-                info_log.as_mut_ptr() as *mut gl::types::GLchar,
-            );
-            println!("ERROR::SHADER::PROGRAM::COMPILATION_FAILED\n{}", String::from_utf8_lossy(&info_log));
-            return false;
-        }
-        true
+        gl::GetProgramiv(self.program_id(), gl::LINK_STATUS, &mut success);
+        if success == i32::from(gl::TRUE) {
+            return None;
+        }
+
+        let mut log_length = 0;
+        gl::GetProgramiv(self.program_id(), gl::INFO_LOG_LENGTH, &mut log_length);
+        let mut info_log = vec![0u8; log_length.max(1) as usize];
+        let mut written = 0;
+        gl::GetProgramInfoLog(
+            self.program_id(),
+            log_length,
+            &mut written,
+            info_log.as_mut_ptr() as *mut gl::types::GLchar,
+        );
+        info_log.truncate(written as usize);
+        Some(String::from_utf8_lossy(&info_log).to_string())
     }
 
     #[must_use = "The shader program is useless if not stored in a variable."]
-    pub unsafe fn link(self) -> Shader {
+    pub unsafe fn link(mut self) -> Result<Shader, ShaderError> {
+        let cache_key = match &self.binary_cache {
+            Some(_) if ProgramBinaryCache::supported() => Some(ProgramBinaryCache::key(
+                self.stages.iter().map(|stage| (stage.shader_type.into(), stage.fingerprint())),
+            )),
+            _ => None,
+        };
+        if let (Some(cache), Some(key)) = (&self.binary_cache, cache_key) {
+            if cache.load(self.program_id(), key) {
+                return self.finish();
+            }
+            // The driver rejected the binary or there was none, so build it the slow way
+            gl::ProgramParameteri(self.program_id(), gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as i32);
+        }
+
+        let stages = std::mem::take(&mut self.stages);
+        for stage in &stages {
+            self.compile(stage)?;
+        }
         for &shader in &self.shaders {
-            gl::AttachShader(self.program_id, shader);
+            gl::AttachShader(self.program_id(), shader);
+        }
+        // Vertex shader inputs get the location of the mesh attribute with the same name
+        for &(name, location) in &vertex_layout::ATTRIBUTES {
+            let name = CString::new(name).unwrap();
+            gl::BindAttribLocation(self.program_id(), location, name.as_ptr());
         }
-        gl::LinkProgram(self.program_id);
+        gl::LinkProgram(self.program_id());
 
-        // todo:: use this to make safer abstraction
-        self.check_linker_errors();
+        if let Some(log) = self.check_linker_errors() {
+            return Err(ShaderError::Link {
+                paths: self.paths.clone(),
+                log,
+            });
+        }
 
-        for &shader in &self.shaders {
-            gl::DeleteShader(shader);
+        if let (Some(cache), Some(key)) = (&self.binary_cache, cache_key) {
+            cache.store(self.program_id(), key);
         }
+        self.finish()
+    }
+
+    unsafe fn finish(&mut self) -> Result<Shader, ShaderError> {
+        // The program now belongs to the shader, the stages are cleaned up when the builder is dropped
+        let program = self.program.take().expect("A shader builder is only finished once");
+        let interface = ProgramInterface::query(program.id());
+        let shader = Shader {
+            program,
+            uniforms: Shader::uniform_cache(&interface),
+            interface,
+            element_uniforms: RefCell::new(HashMap::new()),
+            sources: std::mem::take(&mut self.sources),
+        };
+        self.check_attributes(&shader)?;
+        Ok(shader)
+    }
+
+    /// Check that every vertex shader input is at the location of the mesh attribute with its
+    /// name. This fails for inputs no mesh has, and for ones moved with `layout(location)`.
+    fn check_attributes(&self, shader: &Shader) -> Result<(), ShaderError> {
+        for attribute in shader.attributes() {
+            // Built-ins like `gl_VertexID` have no location
+            let location = match attribute.location {
+                Some(location) => location,
+                None => continue,
+            };
+            let expected = vertex_layout::attribute_location(&attribute.name);
+            if expected != Some(location) {
+                return Err(ShaderError::Attribute {
+                    paths: self.paths.clone(),
+                    name: attribute.name.clone(),
+                    location,
+                    expected,
+                });
+            }
+        }
+        Ok(())
+    }
+}
 
-        Shader {
-            program_id: self.program_id
+impl Drop for ShaderBuilder {
+    fn drop(&mut self) {
+        unsafe {
+            for &shader in &self.shaders {
+                gl::DeleteShader(shader);
+            }
         }
     }
 }
@@ -0,0 +1,163 @@
+use crate::util;
+use gl::types::{GLenum, GLsizei};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Marks our cache files, so a stray file in the cache directory is never handed to the driver.
+const MAGIC: &[u8; 4] = b"GLPB";
+
+/// Stores linked program binaries on disk, so later runs can skip compiling and linking.
+///
+/// Binaries are keyed by a hash of the preprocessed sources and the driver vendor, renderer and
+/// version, since drivers only accept binaries they produced themselves. Even then a driver
+/// may reject an old binary, in which case the caller has to compile the program again.
+#[derive(Clone, Debug)]
+pub struct ProgramBinaryCache {
+    directory: PathBuf,
+}
+
+impl ProgramBinaryCache {
+    pub fn new<P: AsRef<Path>>(directory: P) -> ProgramBinaryCache {
+        ProgramBinaryCache {
+            directory: directory.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Whether the driver can save and load program binaries at all.
+    pub unsafe fn supported() -> bool {
+        let mut formats = 0;
+        gl::GetIntegerv(gl::NUM_PROGRAM_BINARY_FORMATS, &mut formats);
+        formats > 0
+    }
+
+    /// The cache key for a program built from these stages.
+    pub unsafe fn key<I, B>(stages: I) -> u64
+    where
+        I: IntoIterator<Item = (GLenum, B)>,
+        B: AsRef<[u8]>,
+    {
+        let mut hash = Fnv1a::new();
+        for name in &[gl::VENDOR, gl::RENDERER, gl::VERSION] {
+            hash.write(util::get_gl_string(*name).as_bytes());
+            hash.write(&[0]);
+        }
+        for (stage, code) in stages {
+            hash.write(&stage.to_le_bytes());
+            hash.write(code.as_ref());
+            hash.write(&[0]);
+        }
+        hash.finish()
+    }
+
+    /// Load the cached binary into `program_id`. Returns false if there is none, or the driver
+    /// rejected it, in which case the stale file is removed.
+    pub unsafe fn load(&self, program_id: u32, key: u64) -> bool {
+        let path = self.path(key);
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(_) => return false,
+        };
+        if data.len() < 8 || &data[..4] != MAGIC {
+            let _ = fs::remove_file(&path);
+            return false;
+        }
+        let format = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+        let binary = &data[8..];
+        gl::ProgramBinary(
+            program_id,
+            format,
+            binary.as_ptr() as *const _,
+            binary.len() as GLsizei,
+        );
+
+        let mut success = i32::from(gl::FALSE);
+        gl::GetProgramiv(program_id, gl::LINK_STATUS, &mut success);
+        if success != i32::from(gl::TRUE) {
+            let _ = fs::remove_file(&path);
+            return false;
+        }
+        true
+    }
+
+    /// Save the binary of a linked program. Failing to save is not an error, the program
+    /// will just be compiled again next time.
+    pub unsafe fn store(&self, program_id: u32, key: u64) {
+        let mut length = 0;
+        gl::GetProgramiv(program_id, gl::PROGRAM_BINARY_LENGTH, &mut length);
+        if length <= 0 {
+            return;
+        }
+        let mut binary = vec![0u8; length as usize];
+        let mut written = 0;
+        let mut format = 0;
+        gl::GetProgramBinary(
+            program_id,
+            length,
+            &mut written,
+            &mut format,
+            binary.as_mut_ptr() as *mut _,
+        );
+        binary.truncate(written as usize);
+
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&format.to_le_bytes());
+        data.extend_from_slice(&binary);
+        if let Err(e) =
+            fs::create_dir_all(&self.directory).and_then(|_| fs::write(self.path(key), &data))
+        {
+            eprintln!("Could not write to the program binary cache: {}", e);
+        }
+    }
+
+    fn path(&self, key: u64) -> PathBuf {
+        self.directory.join(format!("{:016x}.bin", key))
+    }
+}
+
+/// 64 bit FNV-1a. Unlike `DefaultHasher`, it is guaranteed to give the same keys in every build.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Fnv1a {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fnv1a(bytes: &[u8]) -> u64 {
+        let mut hash = Fnv1a::new();
+        hash.write(bytes);
+        hash.finish()
+    }
+
+    #[test]
+    fn fnv1a_test_vectors() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn fnv1a_does_not_depend_on_how_the_bytes_are_split() {
+        let mut hash = Fnv1a::new();
+        hash.write(b"foo");
+        hash.write(b"bar");
+        assert_eq!(hash.finish(), fnv1a(b"foobar"));
+    }
+}
@@ -0,0 +1,190 @@
+use super::{Shader, ShaderBuilder, ShaderError, ShaderWatcher, Specialization};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+/// Identifies one variant of a shader program: the stage files plus the defines injected into them.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ShaderKey {
+    stages: Vec<String>,
+    // Sorted, so the order the defines were added in does not matter
+    defines: BTreeMap<String, String>,
+}
+
+impl ShaderKey {
+    pub fn new(stages: &[&str]) -> ShaderKey {
+        ShaderKey {
+            stages: stages.iter().map(|stage| stage.to_string()).collect(),
+            defines: BTreeMap::new(),
+        }
+    }
+
+    /// Build this variant with `#define name value`. SPIR-V stages get the defines as
+    /// specialization constants instead, see `Specialization::from_defines`.
+    pub fn define(mut self, name: &str, value: &str) -> ShaderKey {
+        self.defines.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn stages(&self) -> &[String] {
+        &self.stages
+    }
+
+    pub fn defines(&self) -> &BTreeMap<String, String> {
+        &self.defines
+    }
+}
+
+/// Compiles shader variants on first use and keeps them for as long as the cache lives.
+///
+/// All variants are watched for changes to their sources, includes too. A variant that fails
+/// to rebuild keeps its previous program, and one that never built is retried once its files change.
+pub struct ShaderCache {
+    include_paths: Vec<PathBuf>,
+    binary_cache: Option<PathBuf>,
+    variants: HashMap<ShaderKey, Shader>,
+    // Variants that failed to build, with the files to watch for a fix
+    failed: HashMap<ShaderKey, Vec<PathBuf>>,
+    watcher: ShaderWatcher,
+}
+
+impl ShaderCache {
+    pub fn new() -> ShaderCache {
+        ShaderCache {
+            include_paths: vec![],
+            binary_cache: None,
+            variants: HashMap::new(),
+            failed: HashMap::new(),
+            watcher: ShaderWatcher::new::<&Path>(&[]),
+        }
+    }
+
+    /// Add a directory to search for `#include`d files, for every variant.
+    pub fn include_path<P: AsRef<Path>>(mut self, path: P) -> ShaderCache {
+        self.include_paths.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Keep the linked programs in a binary cache in `directory`, to skip compiling on later runs.
+    pub fn binary_cache<P: AsRef<Path>>(mut self, directory: P) -> ShaderCache {
+        self.binary_cache = Some(directory.as_ref().to_path_buf());
+        self
+    }
+
+    /// The program for a variant, compiling it first if it is not cached yet.
+    /// Variants that failed to build are tried again.
+    pub unsafe fn get(&mut self, key: &ShaderKey) -> Result<&Shader, ShaderError> {
+        if !self.variants.contains_key(key) {
+            self.rebuild(key)?;
+        }
+        Ok(&self.variants[key])
+    }
+
+    /// The program for a variant, if it has been built.
+    pub fn variant(&self, key: &ShaderKey) -> Option<&Shader> {
+        self.variants.get(key)
+    }
+
+    pub fn contains(&self, key: &ShaderKey) -> bool {
+        self.variants.contains_key(key)
+    }
+
+    /// Rebuild every variant that uses a source file that changed, returning the errors.
+    pub unsafe fn reload_changed(&mut self) -> Vec<ShaderError> {
+        let changed = self.watcher.changed_files();
+        if changed.is_empty() {
+            return vec![];
+        }
+
+        let uses_changed = |files: &[PathBuf]| files.iter().any(|file| changed.contains(file));
+        let stale: Vec<ShaderKey> = self
+            .variants
+            .iter()
+            .filter(|(_, shader)| uses_changed(shader.sources()))
+            .map(|(key, _)| key.clone())
+            .chain(
+                self.failed
+                    .iter()
+                    .filter(|(_, files)| uses_changed(files))
+                    .map(|(key, _)| key.clone()),
+            )
+            .collect();
+
+        stale
+            .iter()
+            .filter_map(|key| self.rebuild(key).err())
+            .collect()
+    }
+
+    /// Build a variant and replace the cached program. The old program is kept if building fails.
+    unsafe fn rebuild(&mut self, key: &ShaderKey) -> Result<(), ShaderError> {
+        match self.build(key) {
+            Ok(shader) => {
+                self.watcher.add(shader.sources());
+                self.failed.remove(key);
+                // Dropping the previous program deletes it
+                self.variants.insert(key.clone(), shader);
+                Ok(())
+            }
+            Err(error) => {
+                if !self.variants.contains_key(key) {
+                    let files = failed_files(key, &error);
+                    self.watcher.add(&files);
+                    self.failed.insert(key.clone(), files);
+                }
+                Err(error)
+            }
+        }
+    }
+
+    unsafe fn build(&self, key: &ShaderKey) -> Result<Shader, ShaderError> {
+        let mut builder = ShaderBuilder::new();
+        for path in &self.include_paths {
+            builder = builder.include_path(path);
+        }
+        if let Some(directory) = &self.binary_cache {
+            builder = builder.binary_cache(directory);
+        }
+        for (name, value) in &key.defines {
+            builder = builder.define(name, value);
+        }
+        for stage in &key.stages {
+            builder = if stage.ends_with(".spv") {
+                let specialization =
+                    Specialization::from_defines(&key.defines).map_err(|message| {
+                        ShaderError::Spirv {
+                            path: stage.clone(),
+                            message,
+                        }
+                    })?;
+                builder.attach_spirv(stage, specialization)?
+            } else {
+                builder.attach_file(stage)?
+            };
+        }
+        builder.link()
+    }
+}
+
+impl Default for ShaderCache {
+    fn default() -> Self {
+        ShaderCache::new()
+    }
+}
+
+/// The files to watch for a fix to a variant that failed to build.
+fn failed_files(key: &ShaderKey, error: &ShaderError) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = key.stages.iter().map(PathBuf::from).collect();
+    let extra = match error {
+        ShaderError::Compile { files, .. } => files.clone(),
+        ShaderError::Preprocess { path, .. } | ShaderError::Read { path, .. } => vec![path.clone()],
+        _ => vec![],
+    };
+    for file in extra.into_iter().map(PathBuf::from) {
+        if !files.contains(&file) {
+            files.push(file);
+        }
+    }
+    files
+}
@@ -0,0 +1,326 @@
+use super::{Diagnostic, PreprocessedSource, Preprocessor, Severity, ShaderType};
+use naga::{
+    front::glsl::{Frontend, Options},
+    Binding, Module, ScalarKind, ShaderStage, TypeInner,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A stage variable that is passed between shader stages.
+struct Varying {
+    name: String,
+    ty: TypeInner,
+}
+
+/// What a shader that parsed exchanges with the stages before and after it.
+struct Interface {
+    path: PathBuf,
+    inputs: Vec<Varying>,
+    outputs: Vec<Varying>,
+}
+
+/// Validate every shader under `directory` without a GL context, printing the problems found.
+///
+/// Each file is preprocessed like `ShaderBuilder` would and parsed with naga. Afterwards every
+/// fragment shader is checked against every vertex shader, since any two can be combined.
+/// Returns false if anything is wrong.
+pub fn check_shaders(directory: &Path) -> bool {
+    let mut preprocessor = Preprocessor::new();
+    preprocessor.include_path(directory);
+
+    let mut files = vec![];
+    collect_shaders(directory, &mut files);
+    files.sort();
+
+    let mut errors = 0;
+    let mut vertex_shaders = vec![];
+    let mut fragment_shaders = vec![];
+    for path in &files {
+        let shader_type = match ShaderType::from_path(path).ok() {
+            Some(shader_type) => shader_type,
+            None => continue,
+        };
+        let source = match preprocessor.process_file(path) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("{}\n", e);
+                errors += 1;
+                continue;
+            }
+        };
+        let module = match parse(&source, shader_type) {
+            Ok(module) => module,
+            Err(diagnostics) => {
+                for diagnostic in &diagnostics {
+                    eprintln!("{}", diagnostic);
+                }
+                errors += diagnostics
+                    .iter()
+                    .filter(|d| d.severity == Severity::Error)
+                    .count();
+                continue;
+            }
+        };
+
+        let interface = interface(path, &module);
+        match shader_type {
+            ShaderType::Vertex => vertex_shaders.push(interface),
+            ShaderType::Fragment => fragment_shaders.push(interface),
+            _ => {}
+        }
+    }
+
+    for fragment_shader in &fragment_shaders {
+        errors += check_interface(&vertex_shaders, fragment_shader);
+    }
+
+    println!("Checked {} shaders, found {} errors", files.len(), errors);
+    errors == 0
+}
+
+/// Every file below `directory` with a shader stage extension.
+/// Include files are checked through the shaders using them.
+fn collect_shaders(directory: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("Could not read {}: {}", directory.display(), e);
+            return;
+        }
+    };
+    for path in entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+    {
+        if path.is_dir() {
+            collect_shaders(&path, files);
+        } else if ShaderType::from_path(&path).is_ok() {
+            files.push(path);
+        }
+    }
+}
+
+fn parse(source: &PreprocessedSource, shader_type: ShaderType) -> Result<Module, Vec<Diagnostic>> {
+    let stage = match shader_type {
+        ShaderType::Vertex => ShaderStage::Vertex,
+        ShaderType::Fragment => ShaderStage::Fragment,
+        ShaderType::Compute => ShaderStage::Compute,
+        // naga has no tessellation or geometry shaders
+        _ => {
+            return Err(vec![Diagnostic::at(
+                Severity::Warning,
+                &format!("skipped, naga cannot parse {} shaders", shader_type),
+                source,
+                0,
+                1,
+                None,
+            )])
+        }
+    };
+
+    let (code, rewritten) = naga_compatible(&source.code);
+    let mut frontend = Frontend::default();
+    frontend.parse(&Options::from(stage), &code).map_err(|e| {
+        e.errors
+            .iter()
+            .map(|error| {
+                let offset = error.meta.to_range().map_or(0, |range| range.start);
+                let (code_line, file_number, line, column) = locate(&code, offset);
+                // Columns in rewritten lines do not match the file
+                let column = Some(column).filter(|_| !rewritten.contains(&code_line));
+                let message = error.kind.to_string();
+                // Not being able to check something is no reason to fail the check
+                let severity = match error.kind {
+                    naga::front::glsl::ErrorKind::NotImplemented(_) => Severity::Warning,
+                    _ => Severity::Error,
+                };
+                Diagnostic::at(severity, &message, source, file_number, line, column)
+            })
+            .collect()
+    })
+}
+
+/// Rewrite the parts of OpenGL GLSL that naga's Vulkan oriented parser rejects,
+/// without moving any lines. Also returns the indices of the lines that were changed.
+///
+/// naga only takes `#version 440` and up, and wants uniforms outside of blocks to be in a block.
+fn naga_compatible(code: &str) -> (String, Vec<usize>) {
+    let mut output = String::with_capacity(code.len());
+    let mut rewritten = vec![];
+    let mut binding = 0;
+    for (index, line) in code.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("#version") {
+            output.push_str("#version 450 core");
+        } else if let Some(declaration) = loose_uniform(trimmed) {
+            output.push_str(&format!(
+                "layout(std140, binding = {0}) uniform _CheckUniforms{0} {{ {1}; }};",
+                binding, declaration
+            ));
+            binding += 1;
+            rewritten.push(index);
+        } else {
+            output.push_str(line);
+        }
+        output.push('\n');
+    }
+    (output, rewritten)
+}
+
+/// For a one line `uniform layout(location = 1) float time;`,
+/// the declaration without qualifiers: `float time`.
+fn loose_uniform(line: &str) -> Option<String> {
+    let declaration = line.strip_suffix(';')?;
+    if declaration.contains('{') {
+        return None;
+    }
+    // Drop `layout(...)` wherever it is
+    let mut rest = declaration.to_string();
+    while let Some(start) = rest.find("layout") {
+        let end = start + rest[start..].find(')')? + 1;
+        rest.replace_range(start..end, " ");
+    }
+    let mut words = rest.split_whitespace();
+    if words.next()? != "uniform" {
+        return None;
+    }
+    let declaration = words.collect::<Vec<_>>().join(" ");
+    // Opaque types cannot go in a block, so they are left for naga to complain about
+    let opaque = [
+        "sampler", "isampler", "usampler", "image", "iimage", "uimage",
+    ];
+    if opaque.iter().any(|prefix| declaration.starts_with(prefix)) {
+        return None;
+    }
+    Some(declaration)
+}
+
+/// The line index in `code`, and the source string number, line and column of a byte offset into
+/// preprocessed code, following its `#line` directives.
+fn locate(code: &str, offset: usize) -> (usize, usize, usize, usize) {
+    let mut file_number = 0;
+    let mut line = 1;
+    let mut line_start = 0;
+    for (index, text) in code.split_inclusive('\n').enumerate() {
+        if line_start + text.len() > offset {
+            let column = code[line_start..offset].chars().count() + 1;
+            return (index, file_number, line, column);
+        }
+        line_start += text.len();
+        line += 1;
+
+        let mut directive = text.split_whitespace();
+        if directive.next() == Some("#line") {
+            let numbers: Vec<usize> = directive.filter_map(|n| n.parse().ok()).collect();
+            if let Some(&next_line) = numbers.first() {
+                line = next_line;
+            }
+            if let Some(&number) = numbers.get(1) {
+                file_number = number;
+            }
+        }
+    }
+    (0, file_number, line, 1)
+}
+
+fn interface(path: &Path, module: &Module) -> Interface {
+    let mut inputs = vec![];
+    let mut outputs = vec![];
+    if let Some(entry_point) = module.entry_points.first() {
+        let function = &entry_point.function;
+        for argument in &function.arguments {
+            if let (Some(name), Some(Binding::Location { .. })) =
+                (&argument.name, &argument.binding)
+            {
+                inputs.push(Varying {
+                    name: name.clone(),
+                    ty: module.types[argument.ty].inner.clone(),
+                });
+            }
+        }
+        if let Some(result) = &function.result {
+            if let TypeInner::Struct { members, .. } = &module.types[result.ty].inner {
+                for member in members {
+                    if let (Some(name), Some(Binding::Location { .. })) =
+                        (&member.name, &member.binding)
+                    {
+                        outputs.push(Varying {
+                            name: name.clone(),
+                            ty: module.types[member.ty].inner.clone(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+    Interface {
+        path: path.to_path_buf(),
+        inputs,
+        outputs,
+    }
+}
+
+/// Check that every input of the fragment shader is written by every vertex shader,
+/// with the same type.
+fn check_interface(vertex_shaders: &[Interface], fragment_shader: &Interface) -> usize {
+    let mut errors = 0;
+    for input in &fragment_shader.inputs {
+        let mut missing = vec![];
+        for vertex_shader in vertex_shaders {
+            match vertex_shader.outputs.iter().find(|o| o.name == input.name) {
+                None => missing.push(file_name(&vertex_shader.path)),
+                Some(output) if output.ty != input.ty => {
+                    eprintln!(
+                        "error: {} reads `{}` as {}, but {} writes it as {}\n",
+                        fragment_shader.path.display(),
+                        input.name,
+                        type_name(&input.ty),
+                        vertex_shader.path.display(),
+                        type_name(&output.ty)
+                    );
+                    errors += 1;
+                }
+                Some(_) => {}
+            }
+        }
+        if !missing.is_empty() {
+            eprintln!(
+                "error: {} reads `{}`, which is not written by {}\n",
+                fragment_shader.path.display(),
+                input.name,
+                missing.join(", ")
+            );
+            errors += 1;
+        }
+    }
+    errors
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map_or_else(
+        || path.display().to_string(),
+        |name| name.to_string_lossy().to_string(),
+    )
+}
+
+fn type_name(ty: &TypeInner) -> String {
+    let prefix = |kind: ScalarKind| match kind {
+        ScalarKind::Sint => "i",
+        ScalarKind::Uint => "u",
+        ScalarKind::Bool => "b",
+        _ => "",
+    };
+    match ty {
+        TypeInner::Scalar(scalar) => match scalar.kind {
+            ScalarKind::Sint => "int".to_string(),
+            ScalarKind::Uint => "uint".to_string(),
+            ScalarKind::Bool => "bool".to_string(),
+            _ => "float".to_string(),
+        },
+        TypeInner::Vector { size, scalar } => format!("{}vec{}", prefix(scalar.kind), *size as u8),
+        TypeInner::Matrix { columns, rows, .. } => format!("mat{}x{}", *columns as u8, *rows as u8),
+        other => format!("{:?}", other),
+    }
+}
@@ -0,0 +1,207 @@
+use super::PreprocessedSource;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// One message from a shader compiler log, mapped back to the file and line it refers to.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// `None` if the driver used a source string number we did not hand out.
+    pub file: Option<String>,
+    pub line: usize,
+    /// Only reported by Mesa. Starts at 1.
+    pub column: Option<usize>,
+    pub source_line: Option<String>,
+}
+
+impl Diagnostic {
+    /// Parse a compiler info log into diagnostics. Understands the formats of
+    ///
+    ///   Mesa:   `0:12(5): error: message`
+    ///   NVIDIA: `0(12) : error C1008: message`
+    ///   AMD:    `ERROR: 0:12: message`
+    ///
+    /// Lines that do not start a new diagnostic are added to the message of the one before them.
+    /// Returns nothing if the log is in a format we do not know.
+    pub fn parse_log(log: &str, source: &PreprocessedSource) -> Vec<Diagnostic> {
+        let mut diagnostics: Vec<Diagnostic> = vec![];
+        for line in log.lines().map(str::trim_end).filter(|l| !l.is_empty()) {
+            match parse_line(line) {
+                Some((file_number, line, column, severity, message)) => diagnostics.push(
+                    Diagnostic::at(severity, message, source, file_number, line, column),
+                ),
+                // Summaries like AMD's `ERROR: 2 compilation errors.` repeat what we already have
+                None if line.starts_with("ERROR:") || line.starts_with("WARNING:") => {}
+                None => {
+                    if let Some(last) = diagnostics.last_mut() {
+                        last.message.push('\n');
+                        last.message.push_str(line.trim());
+                    }
+                }
+            }
+        }
+        diagnostics
+    }
+
+    /// A diagnostic for `line` of source string `file_number` of the preprocessed source.
+    pub fn at(
+        severity: Severity,
+        message: &str,
+        source: &PreprocessedSource,
+        file_number: usize,
+        line: usize,
+        column: Option<usize>,
+    ) -> Diagnostic {
+        Diagnostic {
+            severity,
+            message: message.to_string(),
+            file: source
+                .files
+                .get(file_number)
+                .map(|f| f.display().to_string()),
+            line,
+            column,
+            source_line: source
+                .contents
+                .get(file_number)
+                .and_then(|text| text.lines().nth(line.checked_sub(1)?))
+                .map(str::to_string),
+        }
+    }
+
+    /// Where to put the caret and how wide to make it, as (start, length) in characters.
+    fn highlight(&self, source_line: &str) -> (usize, usize) {
+        let length = source_line.chars().count();
+        if let Some(column) = self.column {
+            // Underline the whole identifier the column points at
+            let start = column.saturating_sub(1).min(length);
+            let word = source_line
+                .chars()
+                .skip(start)
+                .take_while(|c| c.is_alphanumeric() || *c == '_')
+                .count();
+            return (start, word.max(1));
+        }
+        // The offending token is usually quoted in the message
+        for quote in &['\'', '"', '`'] {
+            let token = self.message.split(*quote).nth(1).map(str::trim);
+            if let Some(token) = token.filter(|t| !t.is_empty()) {
+                if let Some(position) = source_line.find(token) {
+                    let start = source_line[..position].chars().count();
+                    return (start, token.chars().count());
+                }
+            }
+        }
+        let indent = source_line
+            .chars()
+            .take_while(|c| c.is_whitespace())
+            .count();
+        (indent, (length - indent).max(1))
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}: {}", self.severity, self.message)?;
+        let file = self.file.as_deref().unwrap_or("<unknown source>");
+        match self.column {
+            Some(column) => writeln!(f, "  --> {}:{}:{}", file, self.line, column)?,
+            None => writeln!(f, "  --> {}:{}", file, self.line)?,
+        }
+
+        if let Some(source_line) = &self.source_line {
+            let gutter = " ".repeat(self.line.to_string().len());
+            let (start, length) = self.highlight(source_line);
+            writeln!(f, "{} |", gutter)?;
+            writeln!(f, "{} | {}", self.line, source_line)?;
+            writeln!(
+                f,
+                "{} | {}{}",
+                gutter,
+                " ".repeat(start),
+                "^".repeat(length)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+type ParsedLine<'a> = (usize, usize, Option<usize>, Severity, &'a str);
+
+fn parse_line(line: &str) -> Option<ParsedLine<'_>> {
+    parse_amd(line)
+        .or_else(|| parse_mesa(line))
+        .or_else(|| parse_nvidia(line))
+}
+
+/// `ERROR: 0:12: message`
+fn parse_amd(line: &str) -> Option<ParsedLine<'_>> {
+    let (severity, rest) = if let Some(rest) = line.strip_prefix("ERROR:") {
+        (Severity::Error, rest)
+    } else if let Some(rest) = line.strip_prefix("WARNING:") {
+        (Severity::Warning, rest)
+    } else {
+        return None;
+    };
+    let (file, rest) = number(rest.trim_start())?;
+    let (line, rest) = number(rest.strip_prefix(':')?)?;
+    let message = rest.strip_prefix(':')?.trim();
+    Some((file, line, None, severity, message))
+}
+
+/// `0:12(5): error: message`
+fn parse_mesa(line: &str) -> Option<ParsedLine<'_>> {
+    let (file, rest) = number(line)?;
+    let (line, rest) = number(rest.strip_prefix(':')?)?;
+    let (column, rest) = number(rest.strip_prefix('(')?)?;
+    let (severity, message) = severity(rest.strip_prefix("):")?)?;
+    Some((file, line, Some(column), severity, message))
+}
+
+/// `0(12) : error C1008: message`
+fn parse_nvidia(line: &str) -> Option<ParsedLine<'_>> {
+    let (file, rest) = number(line)?;
+    let (line, rest) = number(rest.strip_prefix('(')?)?;
+    let (severity, message) = severity(rest.strip_prefix(')')?.trim_start().strip_prefix(':')?)?;
+    Some((file, line, None, severity, message))
+}
+
+/// Split `error: message` or `error C1008: message` into its parts.
+fn severity(text: &str) -> Option<(Severity, &str)> {
+    // Mesa reports `preprocessor error:` for errors in directives
+    let text = text.trim_start();
+    let text = text.strip_prefix("preprocessor ").unwrap_or(text);
+    let (severity, rest) = if let Some(rest) = text.strip_prefix("error") {
+        (Severity::Error, rest)
+    } else if let Some(rest) = text.strip_prefix("warning") {
+        (Severity::Warning, rest)
+    } else {
+        return None;
+    };
+    // Skip an error code like `C1008`
+    let (_code, message) = rest.split_once(':')?;
+    Some((severity, message.trim()))
+}
+
+/// Parse the number at the start of `text`, returning the rest of the text as well.
+fn number(text: &str) -> Option<(usize, &str)> {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    Some((text[..end].parse().ok()?, &text[end..]))
+}
@@ -0,0 +1,139 @@
+use super::{Diagnostic, ShaderType};
+use crate::vertex_layout;
+use std::{fmt, io};
+
+/// Everything that can go wrong while building a shader program.
+#[derive(Debug)]
+pub enum ShaderError {
+    /// The shader source could not be read from disk.
+    Read { path: String, error: io::Error },
+    /// The stage could not be determined from the file extension.
+    UnknownExtension { path: String },
+    /// A SPIR-V module that is malformed, or SPIR-V is not supported by the driver.
+    Spirv { path: String, message: String },
+    /// A SPIR-V stage attached to a program with GLSL stages, or the other way around.
+    MixedStages { path: String },
+    /// A broken or unresolvable `#include`.
+    Preprocess {
+        path: String,
+        line: usize,
+        message: String,
+    },
+    /// A stage failed to compile.
+    /// `files` maps the source string numbers in the log to the files they came from.
+    /// `diagnostics` is the log parsed into messages, empty if the driver's format is unknown.
+    Compile {
+        path: String,
+        stage: ShaderType,
+        log: String,
+        files: Vec<String>,
+        diagnostics: Vec<Diagnostic>,
+    },
+    /// The stages compiled, but could not be linked into a program.
+    Link { paths: Vec<String>, log: String },
+    /// A vertex shader input that meshes do not have, or that is not at the location meshes
+    /// have it at. `expected` is `None` if no mesh attribute has that name.
+    Attribute {
+        paths: Vec<String>,
+        name: String,
+        location: u32,
+        expected: Option<u32>,
+    },
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderError::Read { path, error } => {
+                write!(f, "Failed to read shader source {}: {}", path, error)
+            }
+            ShaderError::UnknownExtension { path } => write!(
+                f,
+                "Cannot tell the shader stage of {} from its extension",
+                path
+            ),
+            ShaderError::Spirv { path, message } => {
+                write!(f, "Cannot load SPIR-V shader {}: {}", path, message)
+            }
+            ShaderError::MixedStages { path } => write!(
+                f,
+                "Cannot attach shader {}: a program cannot mix SPIR-V and GLSL stages",
+                path
+            ),
+            ShaderError::Preprocess {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path, line, message),
+            ShaderError::Compile {
+                path,
+                stage,
+                log,
+                files,
+                diagnostics,
+            } => {
+                writeln!(f, "Failed to compile {} shader {}:", stage, path)?;
+                if !diagnostics.is_empty() {
+                    for diagnostic in diagnostics {
+                        writeln!(f, "{}", diagnostic)?;
+                    }
+                    return Ok(());
+                }
+                write!(f, "{}", log)?;
+                if files.len() > 1 {
+                    writeln!(f, "Source string numbers:")?;
+                    for (number, file) in files.iter().enumerate() {
+                        writeln!(f, "  {}: {}", number, file)?;
+                    }
+                }
+                Ok(())
+            }
+            ShaderError::Link { paths, log } => write!(
+                f,
+                "Failed to link shader program ({}):\n{}",
+                paths.join(", "),
+                log
+            ),
+            ShaderError::Attribute {
+                paths,
+                name,
+                location: _,
+                expected: None,
+            } => {
+                let names: Vec<&str> = vertex_layout::ATTRIBUTES
+                    .iter()
+                    .map(|&(name, _)| name)
+                    .collect();
+                write!(
+                    f,
+                    "Shader program ({}) reads the vertex input {}, but meshes only have {}",
+                    paths.join(", "),
+                    name,
+                    names.join(", ")
+                )
+            }
+            ShaderError::Attribute {
+                paths,
+                name,
+                location,
+                expected: Some(expected),
+            } => write!(
+                f,
+                "Shader program ({}) has the vertex input {} at location {} instead of {}",
+                paths.join(", "),
+                name,
+                location,
+                expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ShaderError::Read { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
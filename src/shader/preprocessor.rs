@@ -0,0 +1,349 @@
+use super::ShaderError;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Used when a shader does not start with its own `#version` directive.
+const DEFAULT_VERSION: &str = "430 core";
+
+/// Resolves `#include "file"` directives and injects defines before a shader is compiled.
+///
+/// Each file gets a source string number, and `#line` directives are emitted around every include,
+/// so the line numbers in compiler errors point into the right file. The numbers index into
+/// `PreprocessedSource::files`.
+#[derive(Clone, Debug)]
+pub struct Preprocessor {
+    include_paths: Vec<PathBuf>,
+    defines: Vec<(String, String)>,
+}
+
+/// The expanded source together with every file that went into it.
+pub struct PreprocessedSource {
+    pub code: String,
+    /// Source string number `i` in `#line` directives refers to `files[i]`.
+    pub files: Vec<PathBuf>,
+    /// The text of each file as it was read, to show the offending lines of compiler errors.
+    pub contents: Vec<String>,
+}
+
+impl Preprocessor {
+    pub fn new() -> Preprocessor {
+        Preprocessor {
+            include_paths: vec![],
+            defines: vec![],
+        }
+    }
+
+    /// Directory searched for includes that are not found next to the including file.
+    pub fn include_path<P: AsRef<Path>>(&mut self, path: P) {
+        self.include_paths.push(path.as_ref().to_path_buf());
+    }
+
+    /// Add `#define name value` to the top of every shader. Redefining a name replaces the old value.
+    pub fn define(&mut self, name: &str, value: &str) {
+        self.defines.retain(|(n, _)| n != name);
+        self.defines.push((name.to_string(), value.to_string()));
+    }
+
+    pub fn process_file(&self, path: &Path) -> Result<PreprocessedSource, ShaderError> {
+        let source = read(path)?;
+        self.process(&source, path)
+    }
+
+    fn process(&self, source: &str, path: &Path) -> Result<PreprocessedSource, ShaderError> {
+        let root = path.to_path_buf();
+        let mut state = State {
+            files: vec![root.clone()],
+            contents: vec![source.to_string()],
+            once: vec![],
+            stack: vec![],
+        };
+
+        // `#version` has to come first, and `#extension` before any code, so both are hoisted
+        let mut version = format!("#version {}", DEFAULT_VERSION);
+        let mut extensions = vec![];
+        let mut body = String::new();
+        let mut header_done = false;
+        for line in source.lines() {
+            let directive = line.trim_start();
+            if !header_done && directive.starts_with("#version") {
+                version = directive.to_string();
+                body.push('\n');
+            } else if !header_done && directive.starts_with("#extension") {
+                extensions.push(directive.to_string());
+                body.push('\n');
+            } else {
+                if !directive.is_empty() && !directive.starts_with("//") {
+                    header_done = true;
+                }
+                body.push_str(line);
+                body.push('\n');
+            }
+        }
+
+        let mut code = version;
+        code.push('\n');
+        for extension in extensions {
+            code.push_str(&extension);
+            code.push('\n');
+        }
+        for (name, value) in &self.defines {
+            code.push_str(&format!("#define {} {}\n", name, value));
+        }
+        code.push_str("#line 1 0\n");
+        self.expand(&body, &root, 0, &mut state, &mut code)?;
+
+        Ok(PreprocessedSource {
+            code,
+            files: state.files,
+            contents: state.contents,
+        })
+    }
+
+    /// Append `source` to `output`, replacing include directives with the included files.
+    fn expand(
+        &self,
+        source: &str,
+        path: &Path,
+        file_number: usize,
+        state: &mut State,
+        output: &mut String,
+    ) -> Result<(), ShaderError> {
+        state.stack.push(file_key(path));
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let directive = line.trim_start();
+
+            if directive.starts_with("#pragma") && directive["#pragma".len()..].trim() == "once" {
+                state.once.push(file_key(path));
+                output.push('\n');
+                continue;
+            }
+
+            let include = match directive.strip_prefix("#include") {
+                Some(rest) => rest.trim(),
+                None => {
+                    output.push_str(line);
+                    output.push('\n');
+                    continue;
+                }
+            };
+
+            let error = |message: String| ShaderError::Preprocess {
+                path: path.display().to_string(),
+                line: line_number,
+                message,
+            };
+            let name = include
+                .strip_prefix('"')
+                .and_then(|rest| rest.split('"').next())
+                .filter(|name| !name.is_empty())
+                .ok_or_else(|| error(format!("Expected #include \"file\", found '{}'", line)))?;
+            let include_path = self
+                .resolve(name, path)
+                .ok_or_else(|| error(format!("Could not find include file \"{}\"", name)))?;
+
+            let include_key = file_key(&include_path);
+            if state.stack.contains(&include_key) {
+                return Err(error(format!("\"{}\" includes itself", name)));
+            }
+            if state.once.contains(&include_key) {
+                output.push('\n');
+                continue;
+            }
+
+            let included_source = read(&include_path)?;
+            let include_number = match state.files.iter().position(|f| file_key(f) == include_key) {
+                Some(number) => number,
+                None => {
+                    state.files.push(include_path.clone());
+                    state.contents.push(included_source.clone());
+                    state.files.len() - 1
+                }
+            };
+            output.push_str(&format!("#line 1 {}\n", include_number));
+            self.expand(
+                &included_source,
+                &include_path,
+                include_number,
+                state,
+                output,
+            )?;
+            output.push_str(&format!("#line {} {}\n", line_number + 1, file_number));
+        }
+        state.stack.pop();
+        Ok(())
+    }
+
+    /// Look next to the including file first, then in the include paths.
+    fn resolve(&self, name: &str, including_file: &Path) -> Option<PathBuf> {
+        let relative = including_file.parent().map(|dir| dir.join(name));
+        relative
+            .into_iter()
+            .chain(self.include_paths.iter().map(|dir| dir.join(name)))
+            .find(|candidate| candidate.is_file())
+    }
+}
+
+impl Default for Preprocessor {
+    fn default() -> Self {
+        Preprocessor::new()
+    }
+}
+
+/// Bookkeeping while expanding one shader. `once` and `stack` hold canonical paths.
+struct State {
+    files: Vec<PathBuf>,
+    contents: Vec<String>,
+    /// Files marked with `#pragma once` that have already been included.
+    once: Vec<PathBuf>,
+    /// The chain of files currently being expanded, to catch recursive includes.
+    stack: Vec<PathBuf>,
+}
+
+/// The same file can be reached through different relative paths, so files are compared canonically.
+fn file_key(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn read(path: &Path) -> Result<String, ShaderError> {
+    fs::read_to_string(path).map_err(|error| ShaderError::Read {
+        path: path.display().to_string(),
+        error,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory with `files` in it, as (name, contents).
+    fn directory(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("gloom-rs-preprocessor-{}", name));
+        let _ = fs::remove_dir_all(&directory);
+        for (file, contents) in files {
+            let path = directory.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        directory
+    }
+
+    #[test]
+    fn header_comes_first() {
+        let directory = directory(
+            "header",
+            &[(
+                "a.frag",
+                "#version 450 core\n#extension GL_ARB_gpu_shader5 : enable\nvoid main() {}\n",
+            )],
+        );
+        let mut preprocessor = Preprocessor::new();
+        preprocessor.define("LIGHTS", "4");
+        preprocessor.define("LIGHTS", "8");
+        let source = preprocessor
+            .process_file(&directory.join("a.frag"))
+            .unwrap();
+        let lines: Vec<&str> = source.code.lines().collect();
+        assert_eq!(
+            lines[..4],
+            [
+                "#version 450 core",
+                "#extension GL_ARB_gpu_shader5 : enable",
+                "#define LIGHTS 8",
+                "#line 1 0",
+            ]
+        );
+        // The hoisted lines are left empty, so the line numbers stay the same
+        assert_eq!(lines[4..], ["", "", "void main() {}"]);
+    }
+
+    #[test]
+    fn default_version() {
+        let directory = directory("version", &[("a.vert", "void main() {}\n")]);
+        let source = Preprocessor::new()
+            .process_file(&directory.join("a.vert"))
+            .unwrap();
+        assert!(source.code.starts_with("#version 430 core\n"));
+    }
+
+    #[test]
+    fn includes_get_line_directives() {
+        let directory = directory(
+            "include",
+            &[
+                ("a.frag", "// a\n#include \"common.glsl\"\nvoid main() {}\n"),
+                ("common.glsl", "float x;\n"),
+            ],
+        );
+        let source = Preprocessor::new()
+            .process_file(&directory.join("a.frag"))
+            .unwrap();
+        assert!(source
+            .code
+            .ends_with("#line 1 0\n// a\n#line 1 1\nfloat x;\n#line 3 0\nvoid main() {}\n"));
+        assert_eq!(source.files.len(), 2);
+        assert!(source.files[1].ends_with("common.glsl"));
+        assert_eq!(source.contents[1], "float x;\n");
+    }
+
+    #[test]
+    fn include_paths_are_searched_after_the_including_directory() {
+        let directory = directory(
+            "include-path",
+            &[
+                ("shaders/a.frag", "#include \"lib.glsl\"\n"),
+                ("shaders/lib.glsl", "float near;\n"),
+                ("library/lib.glsl", "float far;\n"),
+                ("library/only.glsl", "float only;\n"),
+                ("shaders/b.frag", "#include \"only.glsl\"\n"),
+            ],
+        );
+        let mut preprocessor = Preprocessor::new();
+        preprocessor.include_path(directory.join("library"));
+        let near = preprocessor
+            .process_file(&directory.join("shaders/a.frag"))
+            .unwrap();
+        assert!(near.code.contains("float near;"));
+        let only = preprocessor
+            .process_file(&directory.join("shaders/b.frag"))
+            .unwrap();
+        assert!(only.code.contains("float only;"));
+    }
+
+    #[test]
+    fn pragma_once() {
+        let directory = directory(
+            "once",
+            &[
+                ("a.frag", "#include \"once.glsl\"\n#include \"once.glsl\"\n"),
+                ("once.glsl", "#pragma once\nfloat x;\n"),
+            ],
+        );
+        let source = Preprocessor::new()
+            .process_file(&directory.join("a.frag"))
+            .unwrap();
+        assert_eq!(source.code.matches("float x;").count(), 1);
+    }
+
+    #[test]
+    fn include_errors() {
+        let directory = directory(
+            "errors",
+            &[
+                ("missing.frag", "\n#include \"nowhere.glsl\"\n"),
+                ("malformed.frag", "#include <common.glsl>\n"),
+                ("loop.glsl", "#include \"loop.glsl\"\n"),
+            ],
+        );
+        let error = |file: &str| match Preprocessor::new().process_file(&directory.join(file)) {
+            Err(ShaderError::Preprocess { line, message, .. }) => (line, message),
+            Err(error) => panic!("Expected a preprocessor error, got {}", error),
+            Ok(_) => panic!("Expected {} to fail", file),
+        };
+        assert_eq!(error("missing.frag").0, 2);
+        assert!(error("malformed.frag").1.contains("Expected #include"));
+        assert!(error("loop.glsl").1.contains("includes itself"));
+    }
+}
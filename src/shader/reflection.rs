@@ -0,0 +1,137 @@
+use gl::types::{GLenum, GLint};
+use std::convert::TryFrom;
+
+/// A vertex shader input.
+#[derive(Clone, Debug)]
+pub struct Attribute {
+    pub name: String,
+    /// Built-ins like `gl_VertexID` have no location.
+    pub location: Option<u32>,
+}
+
+/// A uniform of the default block or of a named uniform block.
+#[derive(Clone, Debug)]
+pub struct ActiveUniform {
+    pub name: String,
+    pub gl_type: GLenum,
+    pub size: i32,
+    /// Uniforms inside blocks have no location.
+    pub location: Option<i32>,
+}
+
+/// A uniform block.
+#[derive(Clone, Debug)]
+pub struct InterfaceBlock {
+    pub name: String,
+    pub binding: u32,
+    pub data_size: i32,
+}
+
+/// Everything a linked program exposes to the application.
+#[derive(Clone, Debug, Default)]
+pub struct ProgramInterface {
+    pub attributes: Vec<Attribute>,
+    pub uniforms: Vec<ActiveUniform>,
+    pub uniform_blocks: Vec<InterfaceBlock>,
+}
+
+impl ProgramInterface {
+    /// Query the active resources of a linked program.
+    pub unsafe fn query(program_id: u32) -> ProgramInterface {
+        let attributes = (0..resource_count(program_id, gl::PROGRAM_INPUT))
+            .map(|index| {
+                let [location] =
+                    resource_properties(program_id, gl::PROGRAM_INPUT, index, [gl::LOCATION]);
+                Attribute {
+                    name: resource_name(program_id, gl::PROGRAM_INPUT, index),
+                    location: u32::try_from(location).ok(),
+                }
+            })
+            .collect();
+
+        let uniforms = (0..resource_count(program_id, gl::UNIFORM))
+            .map(|index| {
+                let [gl_type, size, location] = resource_properties(
+                    program_id,
+                    gl::UNIFORM,
+                    index,
+                    [gl::TYPE, gl::ARRAY_SIZE, gl::LOCATION],
+                );
+                ActiveUniform {
+                    name: resource_name(program_id, gl::UNIFORM, index),
+                    gl_type: gl_type as GLenum,
+                    size,
+                    location: Some(location).filter(|&l| l >= 0),
+                }
+            })
+            .collect();
+
+        let uniform_blocks = (0..resource_count(program_id, gl::UNIFORM_BLOCK))
+            .map(|index| {
+                let [binding, data_size] = resource_properties(
+                    program_id,
+                    gl::UNIFORM_BLOCK,
+                    index,
+                    [gl::BUFFER_BINDING, gl::BUFFER_DATA_SIZE],
+                );
+                InterfaceBlock {
+                    name: resource_name(program_id, gl::UNIFORM_BLOCK, index),
+                    binding: binding as u32,
+                    data_size,
+                }
+            })
+            .collect();
+
+        ProgramInterface {
+            attributes,
+            uniforms,
+            uniform_blocks,
+        }
+    }
+
+    pub fn uniform_block(&self, name: &str) -> Option<&InterfaceBlock> {
+        self.uniform_blocks.iter().find(|b| b.name == name)
+    }
+}
+
+unsafe fn resource_count(program_id: u32, interface: GLenum) -> u32 {
+    let mut count = 0;
+    gl::GetProgramInterfaceiv(program_id, interface, gl::ACTIVE_RESOURCES, &mut count);
+    count as u32
+}
+
+unsafe fn resource_name(program_id: u32, interface: GLenum, index: u32) -> String {
+    let [length] = resource_properties(program_id, interface, index, [gl::NAME_LENGTH]);
+    let mut name = vec![0u8; length.max(1) as usize];
+    let mut written = 0;
+    gl::GetProgramResourceName(
+        program_id,
+        interface,
+        index,
+        length,
+        &mut written,
+        name.as_mut_ptr() as *mut gl::types::GLchar,
+    );
+    name.truncate(written as usize);
+    String::from_utf8_lossy(&name).to_string()
+}
+
+unsafe fn resource_properties<const N: usize>(
+    program_id: u32,
+    interface: GLenum,
+    index: u32,
+    properties: [GLenum; N],
+) -> [GLint; N] {
+    let mut values = [0; N];
+    gl::GetProgramResourceiv(
+        program_id,
+        interface,
+        index,
+        N as i32,
+        properties.as_ptr(),
+        N as i32,
+        std::ptr::null_mut(),
+        values.as_mut_ptr(),
+    );
+    values
+}
@@ -0,0 +1,231 @@
+use crate::util;
+use gl::types::{GLchar, GLenum, GLuint};
+use std::{
+    ffi::{c_void, CString},
+    mem,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// `GL_SHADER_BINARY_FORMAT_SPIR_V`, the format for `glShaderBinary`. Not in the GL 4.5 bindings.
+pub const SHADER_BINARY_FORMAT_SPIR_V: GLenum = 0x9551;
+
+/// The first word of every SPIR-V module.
+const MAGIC: u32 = 0x0723_0203;
+
+type SpecializeShaderFn =
+    extern "system" fn(GLuint, *const GLchar, GLuint, *const GLuint, *const GLuint);
+
+// `glSpecializeShader` is core in 4.6 and `glSpecializeShaderARB` comes with ARB_gl_spirv,
+// but the `gl` crate only has 4.5, so the function is loaded by hand
+static SPECIALIZE_SHADER: AtomicUsize = AtomicUsize::new(0);
+
+/// Load the entry points for SPIR-V shaders. Call this with the same loader as `gl::load_with`,
+/// after it, with the context current: loaders can return functions the context does not support.
+pub unsafe fn load_with<F>(mut loader: F)
+where
+    F: FnMut(&'static str) -> *const c_void,
+{
+    let function = ["glSpecializeShader", "glSpecializeShaderARB"]
+        .iter()
+        .map(|name| loader(name))
+        .find(|function| !function.is_null())
+        .unwrap_or(std::ptr::null());
+    // SPIR-V is core in GL 4.6
+    let supported = util::has_version_or_extension(4, 6, "GL_ARB_gl_spirv");
+    let function = if !function.is_null() && supported {
+        function as usize
+    } else {
+        0
+    };
+    SPECIALIZE_SHADER.store(function, Ordering::Release);
+}
+
+/// Whether SPIR-V shaders can be used, that is, the context is GL 4.6 or has `ARB_gl_spirv`
+/// and `load_with` found `glSpecializeShader`.
+pub fn supported() -> bool {
+    SPECIALIZE_SHADER.load(Ordering::Acquire) != 0
+}
+
+/// The specialization constant values a SPIR-V module is compiled with. Modules are entered
+/// through `main`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Specialization {
+    constants: Vec<(u32, u32)>,
+}
+
+impl Specialization {
+    pub fn new() -> Specialization {
+        Specialization { constants: vec![] }
+    }
+
+    /// The constants of a shader variant, from its defines. SPIR-V has no preprocessor, so each
+    /// name has to be a `constant_id`, and each value a bool, an integer or a float.
+    pub fn from_defines<'a, I>(defines: I) -> Result<Specialization, String>
+    where
+        I: IntoIterator<Item = (&'a String, &'a String)>,
+    {
+        let mut specialization = Specialization::new();
+        for (name, value) in defines {
+            let id = name.parse().map_err(|_| {
+                format!(
+                    "the define {} is not a constant_id, like 0 in `#define 0 1.5`",
+                    name
+                )
+            })?;
+            specialization = if let Ok(value) = value.parse::<bool>() {
+                specialization.constant(id, value)
+            } else if let Ok(value) = value.parse::<i32>() {
+                specialization.constant(id, value)
+            } else if let Ok(value) = value.parse::<u32>() {
+                specialization.constant(id, value)
+            } else if let Ok(value) = value.parse::<f32>() {
+                specialization.constant(id, value)
+            } else {
+                return Err(format!(
+                    "the value {} of constant {} is not a bool, an integer or a float",
+                    value, id
+                ));
+            };
+        }
+        Ok(specialization)
+    }
+
+    /// Set the constant declared with `layout(constant_id = id)`.
+    pub fn constant<T: SpecializationConstant>(mut self, id: u32, value: T) -> Specialization {
+        self.constants.retain(|&(i, _)| i != id);
+        self.constants.push((id, value.bits()));
+        self
+    }
+
+    /// Bytes that identify this specialization, for cache keys.
+    pub(super) fn fingerprint(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        for (id, value) in &self.constants {
+            bytes.extend_from_slice(&id.to_le_bytes());
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+}
+
+impl Default for Specialization {
+    fn default() -> Self {
+        Specialization::new()
+    }
+}
+
+/// Scalar types a specialization constant can have. The value is passed as its 32 bit pattern.
+pub trait SpecializationConstant {
+    fn bits(self) -> u32;
+}
+
+impl SpecializationConstant for u32 {
+    fn bits(self) -> u32 {
+        self
+    }
+}
+
+impl SpecializationConstant for i32 {
+    fn bits(self) -> u32 {
+        self as u32
+    }
+}
+
+impl SpecializationConstant for f32 {
+    fn bits(self) -> u32 {
+        self.to_bits()
+    }
+}
+
+impl SpecializationConstant for bool {
+    fn bits(self) -> u32 {
+        self as u32
+    }
+}
+
+/// Check that `binary` looks like a SPIR-V module, returning why not if it does not.
+pub fn validate(binary: &[u8]) -> Result<(), &'static str> {
+    if binary.len() < 20 || !binary.len().is_multiple_of(4) {
+        return Err("the file is not a whole number of 32 bit words");
+    }
+    let magic = [binary[0], binary[1], binary[2], binary[3]];
+    if u32::from_le_bytes(magic) != MAGIC && u32::from_be_bytes(magic) != MAGIC {
+        return Err("the file does not start with the SPIR-V magic number");
+    }
+    Ok(())
+}
+
+/// Upload a SPIR-V module to `shader` and specialize it. Check `GL_COMPILE_STATUS` afterwards.
+pub unsafe fn load(shader: GLuint, binary: &[u8], specialization: &Specialization) {
+    gl::ShaderBinary(
+        1,
+        &shader,
+        SHADER_BINARY_FORMAT_SPIR_V,
+        binary.as_ptr() as *const c_void,
+        binary.len() as i32,
+    );
+
+    let function = SPECIALIZE_SHADER.load(Ordering::Acquire);
+    assert!(function != 0, "SPIR-V entry points are not loaded");
+    let specialize_shader: SpecializeShaderFn = mem::transmute(function);
+    let entry_point = CString::new("main").unwrap();
+    let (indices, values): (Vec<u32>, Vec<u32>) = specialization.constants.iter().copied().unzip();
+    specialize_shader(
+        shader,
+        entry_point.as_ptr(),
+        indices.len() as GLuint,
+        indices.as_ptr(),
+        values.as_ptr(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn from_defines(defines: &[(&str, &str)]) -> Result<Specialization, String> {
+        let defines: BTreeMap<String, String> = defines
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        Specialization::from_defines(&defines)
+    }
+
+    #[test]
+    fn constants_from_defines() {
+        let specialization = from_defines(&[
+            ("0", "true"),
+            ("1", "-3"),
+            ("2", "4000000000"),
+            ("3", "1.5"),
+        ])
+        .unwrap();
+        assert_eq!(
+            specialization.constants,
+            [
+                (0, 1),
+                (1, -3i32 as u32),
+                (2, 4_000_000_000),
+                (3, 1.5f32.to_bits())
+            ]
+        );
+        assert_eq!(from_defines(&[]).unwrap(), Specialization::new());
+    }
+
+    #[test]
+    fn defines_that_are_not_constants() {
+        assert!(from_defines(&[("LIGHTING", "1")]).is_err());
+        assert!(from_defines(&[("0", "bright")]).is_err());
+    }
+
+    #[test]
+    fn validate_checks_the_magic_number() {
+        let mut module = MAGIC.to_le_bytes().to_vec();
+        module.resize(20, 0);
+        assert!(validate(&module).is_ok());
+        assert!(validate(&module[..19]).is_err());
+        module[0] = 0;
+        assert!(validate(&module).is_err());
+    }
+}
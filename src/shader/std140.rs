@@ -0,0 +1,330 @@
+use super::Shader;
+use crate::gl_object::Buffer;
+use std::marker::PhantomData;
+
+/// Bytes laid out by the std140 rules, ready to upload to a uniform buffer.
+#[derive(Default)]
+pub struct Std140Buffer {
+    bytes: Vec<u8>,
+}
+
+impl Std140Buffer {
+    pub fn new() -> Std140Buffer {
+        Std140Buffer { bytes: vec![] }
+    }
+
+    /// Append a value at the next offset with the right alignment for it.
+    pub fn push<T: Std140 + ?Sized>(&mut self, value: &T) {
+        self.align(T::ALIGN);
+        value.write_std140(self);
+    }
+
+    /// Pad with zeroes up to a multiple of `alignment`.
+    pub fn align(&mut self, alignment: usize) {
+        let padded = self.bytes.len().div_ceil(alignment) * alignment;
+        self.bytes.resize(padded, 0);
+    }
+
+    fn write_words(&mut self, words: &[u32]) {
+        for word in words {
+            self.bytes.extend_from_slice(&word.to_ne_bytes());
+        }
+    }
+}
+
+/// A type that can be stored in a std140 uniform block.
+pub trait Std140 {
+    /// Base alignment in bytes.
+    const ALIGN: usize;
+
+    /// Write the value, without the padding in front of it.
+    fn write_std140(&self, buffer: &mut Std140Buffer);
+}
+
+/// A struct that is used as a whole uniform block, made with `uniform_block!`.
+pub trait UniformBlock: Std140 {
+    /// The name of the block in GLSL.
+    const NAME: &'static str;
+
+    /// The value in std140 layout.
+    fn std140_bytes(&self) -> Vec<u8> {
+        let mut buffer = Std140Buffer::new();
+        buffer.push(self);
+        buffer.bytes
+    }
+}
+
+/// Scalars and vectors, written as their 32 bit components.
+macro_rules! std140_vector {
+    ($type:ty, $align:expr, |$value:ident| $words:expr) => {
+        impl Std140 for $type {
+            const ALIGN: usize = $align;
+
+            fn write_std140(&self, buffer: &mut Std140Buffer) {
+                let $value = self;
+                buffer.write_words(&$words);
+            }
+        }
+    };
+}
+
+/// Matrices are stored as an array of their column vectors, and every column is aligned like a `vec4`.
+macro_rules! std140_matrix {
+    ($type:ty) => {
+        impl Std140 for $type {
+            const ALIGN: usize = 16;
+
+            fn write_std140(&self, buffer: &mut Std140Buffer) {
+                for column in self.column_iter() {
+                    buffer.align(16);
+                    buffer.write_words(&column.iter().map(|c| c.to_bits()).collect::<Vec<_>>());
+                }
+                buffer.align(16);
+            }
+        }
+    };
+}
+
+std140_vector!(f32, 4, |v| [v.to_bits()]);
+std140_vector!(i32, 4, |v| [*v as u32]);
+std140_vector!(u32, 4, |v| [*v]);
+std140_vector!(bool, 4, |v| [*v as u32]);
+std140_vector!(glm::Vec2, 8, |v| [v.x.to_bits(), v.y.to_bits()]);
+std140_vector!(glm::Vec3, 16, |v| {
+    [v.x.to_bits(), v.y.to_bits(), v.z.to_bits()]
+});
+std140_vector!(glm::Vec4, 16, |v| {
+    [v.x.to_bits(), v.y.to_bits(), v.z.to_bits(), v.w.to_bits()]
+});
+std140_vector!(glm::IVec2, 8, |v| [v.x as u32, v.y as u32]);
+std140_vector!(glm::IVec3, 16, |v| [v.x as u32, v.y as u32, v.z as u32]);
+std140_vector!(glm::IVec4, 16, |v| {
+    [v.x as u32, v.y as u32, v.z as u32, v.w as u32]
+});
+std140_vector!(glm::UVec2, 8, |v| [v.x, v.y]);
+std140_vector!(glm::UVec3, 16, |v| [v.x, v.y, v.z]);
+std140_vector!(glm::UVec4, 16, |v| [v.x, v.y, v.z, v.w]);
+
+std140_matrix!(glm::Mat2);
+std140_matrix!(glm::Mat3);
+std140_matrix!(glm::Mat4);
+std140_matrix!(glm::Mat3x2);
+std140_matrix!(glm::Mat4x2);
+std140_matrix!(glm::Mat2x3);
+std140_matrix!(glm::Mat4x3);
+std140_matrix!(glm::Mat2x4);
+std140_matrix!(glm::Mat3x4);
+
+/// Every array element is aligned like a `vec4`, so a `float[4]` takes 64 bytes.
+impl<T: Std140, const N: usize> Std140 for [T; N] {
+    const ALIGN: usize = 16;
+
+    fn write_std140(&self, buffer: &mut Std140Buffer) {
+        for element in self {
+            buffer.align(16);
+            element.write_std140(buffer);
+        }
+        buffer.align(16);
+    }
+}
+
+/// Define a struct that can be written to a uniform buffer with the std140 layout. The GLSL
+/// declaration has to match the fields, and nested structs have to be defined with this macro too.
+///
+/// ```ignore
+/// uniform_block! {
+///     pub struct FrameData {
+///         pub view: glm::Mat4,
+///         pub time: f32,
+///     }
+/// }
+/// ```
+macro_rules! uniform_block {
+    (
+        $(#[$attribute:meta])*
+        $visibility:vis struct $name:ident {
+            $($(#[$field_attribute:meta])* $field_visibility:vis $field:ident: $type:ty),* $(,)?
+        }
+    ) => {
+        $(#[$attribute])*
+        #[derive(Clone, Copy, Debug)]
+        $visibility struct $name {
+            $($(#[$field_attribute])* $field_visibility $field: $type),*
+        }
+
+        impl $crate::shader::Std140 for $name {
+            const ALIGN: usize = 16;
+
+            fn write_std140(&self, buffer: &mut $crate::shader::Std140Buffer) {
+                $(buffer.push(&self.$field);)*
+                buffer.align(16);
+            }
+        }
+
+        impl $crate::shader::UniformBlock for $name {
+            const NAME: &'static str = stringify!($name);
+        }
+    };
+}
+
+pub(crate) use uniform_block;
+
+/// A uniform buffer holding one `T`, bound to a fixed binding point that every program shares.
+///
+/// Programs declare the block with `layout(std140, binding = N)`, and `verify` checks they match.
+pub struct UniformBuffer<T: UniformBlock> {
+    buffer: Buffer,
+    binding: u32,
+    block: PhantomData<T>,
+}
+
+impl<T: UniformBlock> UniformBuffer<T> {
+    /// Needs a current GL context.
+    pub unsafe fn new(binding: u32) -> UniformBuffer<T> {
+        UniformBuffer {
+            buffer: Buffer::new(),
+            binding,
+            block: PhantomData,
+        }
+    }
+
+    /// Upload a new value and bind the buffer, for all programs at once.
+    pub fn update(&mut self, value: &T) {
+        let bytes = value.std140_bytes();
+        if bytes.len() == self.buffer.size() {
+            self.buffer.set_sub_data(0, &bytes);
+        } else {
+            self.buffer.set_data(&bytes, gl::DYNAMIC_DRAW);
+        }
+        self.bind();
+    }
+
+    pub fn bind(&self) {
+        self.buffer.bind_base(gl::UNIFORM_BUFFER, self.binding);
+    }
+
+    /// Check that `shader` declares the block with the binding and size of the last upload.
+    /// Programs that do not use the block at all are fine.
+    pub fn verify(&self, shader: &Shader) -> Result<(), String> {
+        let block = match shader.interface().uniform_block(T::NAME) {
+            Some(block) => block,
+            None => return Ok(()),
+        };
+        let size = self.buffer.size();
+        if block.binding != self.binding {
+            Err(format!(
+                "Uniform block {} has binding {}, but the buffer is bound to {}",
+                T::NAME,
+                block.binding,
+                self.binding
+            ))
+        } else if size != 0 && block.data_size as usize != size {
+            Err(format!(
+                "Uniform block {} is {} bytes in the shader, but {} bytes in Rust",
+                T::NAME,
+                block.data_size,
+                size
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    uniform_block! {
+        struct Inner {
+            a: f32,
+            b: glm::Vec2,
+        }
+    }
+
+    uniform_block! {
+        struct Outer {
+            direction: glm::Vec3,
+            // Fills the end of `direction`'s 16 bytes
+            intensity: f32,
+            normal: glm::Mat3,
+            weights: [f32; 2],
+            inner: Inner,
+            flag: bool,
+        }
+    }
+
+    fn words(bytes: &[u8]) -> Vec<u32> {
+        bytes
+            .chunks(4)
+            .map(|word| u32::from_ne_bytes([word[0], word[1], word[2], word[3]]))
+            .collect()
+    }
+
+    #[test]
+    fn scalars_and_vectors() {
+        let mut buffer = Std140Buffer::new();
+        buffer.push(&1.0f32);
+        // vec2 is aligned to 8 bytes, vec3 and vec4 to 16
+        buffer.push(&glm::vec2(2.0, 3.0));
+        buffer.push(&glm::vec3(4.0, 5.0, 6.0));
+        buffer.push(&7u32);
+        assert_eq!(
+            words(&buffer.bytes),
+            [
+                1.0f32.to_bits(),
+                0,
+                2.0f32.to_bits(),
+                3.0f32.to_bits(),
+                4.0f32.to_bits(),
+                5.0f32.to_bits(),
+                6.0f32.to_bits(),
+                7
+            ]
+        );
+    }
+
+    #[test]
+    fn matrix_columns_are_padded_to_vec4() {
+        let mut buffer = Std140Buffer::new();
+        buffer.push(&glm::Mat3::identity());
+        let one = 1.0f32.to_bits();
+        assert_eq!(
+            words(&buffer.bytes),
+            [one, 0, 0, 0, 0, one, 0, 0, 0, 0, one, 0]
+        );
+    }
+
+    #[test]
+    fn array_elements_are_padded_to_vec4() {
+        let mut buffer = Std140Buffer::new();
+        buffer.push(&[1u32, 2u32]);
+        assert_eq!(words(&buffer.bytes), [1, 0, 0, 0, 2, 0, 0, 0]);
+    }
+
+    #[test]
+    fn block_layout() {
+        let block = Outer {
+            direction: glm::vec3(1.0, 2.0, 3.0),
+            intensity: 4.0,
+            normal: glm::Mat3::identity(),
+            weights: [5.0, 6.0],
+            inner: Inner {
+                a: 7.0,
+                b: glm::vec2(8.0, 9.0),
+            },
+            flag: true,
+        };
+        let words = words(&block.std140_bytes());
+        // vec3 + float, mat3, float[2], Inner and bool, each padded to 16 bytes
+        assert_eq!(words.len(), (16 + 48 + 32 + 16 + 16) / 4);
+        assert_eq!(words[3], 4.0f32.to_bits());
+        assert_eq!(words[16], 5.0f32.to_bits());
+        assert_eq!(words[20], 6.0f32.to_bits());
+        assert_eq!(
+            words[24..28],
+            [7.0f32.to_bits(), 0, 8.0f32.to_bits(), 9.0f32.to_bits()]
+        );
+        assert_eq!(words[28], 1);
+    }
+}
@@ -0,0 +1,415 @@
+use gl::types::{GLenum, GLint, GLuint};
+use std::fmt;
+
+/// What we know about an active uniform after linking.
+#[derive(Clone, Copy, Debug)]
+pub struct UniformInfo {
+    pub location: GLint,
+    pub gl_type: GLenum,
+    /// Number of array elements, 1 for non-arrays.
+    pub size: i32,
+}
+
+#[derive(Debug)]
+pub enum UniformError {
+    /// The program has no active uniform with this name. The compiler removes unused uniforms.
+    Unknown(String),
+    TypeMismatch {
+        name: String,
+        expected: &'static str,
+        given: &'static str,
+    },
+    TooManyElements {
+        name: String,
+        size: i32,
+        given: usize,
+    },
+}
+
+impl fmt::Display for UniformError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UniformError::Unknown(name) => {
+                write!(
+                    f,
+                    "No active uniform named '{}' in the shader program",
+                    name
+                )
+            }
+            UniformError::TypeMismatch {
+                name,
+                expected,
+                given,
+            } => write!(
+                f,
+                "Uniform '{}' is declared as {} but was given {}",
+                name, expected, given
+            ),
+            UniformError::TooManyElements { name, size, given } => write!(
+                f,
+                "Uniform '{}' holds {} elements but was given {}",
+                name, size, given
+            ),
+        }
+    }
+}
+
+impl std::error::Error for UniformError {}
+
+/// A single value that can be uploaded to a uniform of a matching GLSL type.
+pub trait UniformValue: Sized {
+    /// GLSL name of the type, used in error messages.
+    const GLSL_TYPE: &'static str;
+
+    /// Whether this value may be assigned to a uniform of the given GL type.
+    fn accepts(gl_type: GLenum) -> bool;
+
+    /// Upload consecutive values starting at `location`.
+    unsafe fn upload(program: GLuint, location: GLint, values: &[Self]);
+}
+
+/// Anything that can be passed to `Shader::set`: single values, slices and arrays.
+pub trait Uniform {
+    fn glsl_type(&self) -> &'static str;
+    fn accepts(&self, gl_type: GLenum) -> bool;
+    fn element_count(&self) -> usize;
+    unsafe fn upload(&self, program: GLuint, location: GLint);
+}
+
+impl<T: UniformValue> Uniform for T {
+    fn glsl_type(&self) -> &'static str {
+        T::GLSL_TYPE
+    }
+
+    fn accepts(&self, gl_type: GLenum) -> bool {
+        T::accepts(gl_type)
+    }
+
+    fn element_count(&self) -> usize {
+        1
+    }
+
+    unsafe fn upload(&self, program: GLuint, location: GLint) {
+        T::upload(program, location, std::slice::from_ref(self))
+    }
+}
+
+impl<T: UniformValue> Uniform for [T] {
+    fn glsl_type(&self) -> &'static str {
+        T::GLSL_TYPE
+    }
+
+    fn accepts(&self, gl_type: GLenum) -> bool {
+        T::accepts(gl_type)
+    }
+
+    fn element_count(&self) -> usize {
+        self.len()
+    }
+
+    unsafe fn upload(&self, program: GLuint, location: GLint) {
+        if !self.is_empty() {
+            T::upload(program, location, self)
+        }
+    }
+}
+
+impl<T: UniformValue, const N: usize> Uniform for [T; N] {
+    fn glsl_type(&self) -> &'static str {
+        T::GLSL_TYPE
+    }
+
+    fn accepts(&self, gl_type: GLenum) -> bool {
+        T::accepts(gl_type)
+    }
+
+    fn element_count(&self) -> usize {
+        N
+    }
+
+    unsafe fn upload(&self, program: GLuint, location: GLint) {
+        self[..].upload(program, location)
+    }
+}
+
+/// Implement `UniformValue` for types that are laid out as plain arrays of their components.
+macro_rules! uniform_value {
+    ($type:ty, $glsl:expr, $gl_type:path, $function:ident, $component:ty) => {
+        impl UniformValue for $type {
+            const GLSL_TYPE: &'static str = $glsl;
+
+            fn accepts(gl_type: GLenum) -> bool {
+                gl_type == $gl_type
+            }
+
+            unsafe fn upload(program: GLuint, location: GLint, values: &[Self]) {
+                gl::$function(
+                    program,
+                    location,
+                    values.len() as i32,
+                    values.as_ptr() as *const $component,
+                );
+            }
+        }
+    };
+}
+
+/// Matrices additionally take a transpose flag. nalgebra stores them column-major, like GLSL.
+macro_rules! uniform_matrix {
+    ($type:ty, $glsl:expr, $gl_type:path, $function:ident) => {
+        impl UniformValue for $type {
+            const GLSL_TYPE: &'static str = $glsl;
+
+            fn accepts(gl_type: GLenum) -> bool {
+                gl_type == $gl_type
+            }
+
+            unsafe fn upload(program: GLuint, location: GLint, values: &[Self]) {
+                gl::$function(
+                    program,
+                    location,
+                    values.len() as i32,
+                    gl::FALSE,
+                    values.as_ptr() as *const f32,
+                );
+            }
+        }
+    };
+}
+
+uniform_value!(f32, "float", gl::FLOAT, ProgramUniform1fv, f32);
+uniform_value!(glm::Vec2, "vec2", gl::FLOAT_VEC2, ProgramUniform2fv, f32);
+uniform_value!(glm::Vec3, "vec3", gl::FLOAT_VEC3, ProgramUniform3fv, f32);
+uniform_value!(glm::Vec4, "vec4", gl::FLOAT_VEC4, ProgramUniform4fv, f32);
+uniform_value!(glm::IVec2, "ivec2", gl::INT_VEC2, ProgramUniform2iv, i32);
+uniform_value!(glm::IVec3, "ivec3", gl::INT_VEC3, ProgramUniform3iv, i32);
+uniform_value!(glm::IVec4, "ivec4", gl::INT_VEC4, ProgramUniform4iv, i32);
+uniform_value!(u32, "uint", gl::UNSIGNED_INT, ProgramUniform1uiv, u32);
+uniform_value!(
+    glm::UVec2,
+    "uvec2",
+    gl::UNSIGNED_INT_VEC2,
+    ProgramUniform2uiv,
+    u32
+);
+uniform_value!(
+    glm::UVec3,
+    "uvec3",
+    gl::UNSIGNED_INT_VEC3,
+    ProgramUniform3uiv,
+    u32
+);
+uniform_value!(
+    glm::UVec4,
+    "uvec4",
+    gl::UNSIGNED_INT_VEC4,
+    ProgramUniform4uiv,
+    u32
+);
+
+// GLSL matCxR has C columns and R rows, while glm::MatRxC has R rows and C columns
+uniform_matrix!(glm::Mat2, "mat2", gl::FLOAT_MAT2, ProgramUniformMatrix2fv);
+uniform_matrix!(glm::Mat3, "mat3", gl::FLOAT_MAT3, ProgramUniformMatrix3fv);
+uniform_matrix!(glm::Mat4, "mat4", gl::FLOAT_MAT4, ProgramUniformMatrix4fv);
+uniform_matrix!(
+    glm::Mat3x2,
+    "mat2x3",
+    gl::FLOAT_MAT2x3,
+    ProgramUniformMatrix2x3fv
+);
+uniform_matrix!(
+    glm::Mat4x2,
+    "mat2x4",
+    gl::FLOAT_MAT2x4,
+    ProgramUniformMatrix2x4fv
+);
+uniform_matrix!(
+    glm::Mat2x3,
+    "mat3x2",
+    gl::FLOAT_MAT3x2,
+    ProgramUniformMatrix3x2fv
+);
+uniform_matrix!(
+    glm::Mat4x3,
+    "mat3x4",
+    gl::FLOAT_MAT3x4,
+    ProgramUniformMatrix3x4fv
+);
+uniform_matrix!(
+    glm::Mat2x4,
+    "mat4x2",
+    gl::FLOAT_MAT4x2,
+    ProgramUniformMatrix4x2fv
+);
+uniform_matrix!(
+    glm::Mat3x4,
+    "mat4x3",
+    gl::FLOAT_MAT4x3,
+    ProgramUniformMatrix4x3fv
+);
+
+/// Plain integers may also be assigned to samplers and images, as in GLSL.
+impl UniformValue for i32 {
+    const GLSL_TYPE: &'static str = "int";
+
+    fn accepts(gl_type: GLenum) -> bool {
+        gl_type == gl::INT || is_opaque(gl_type)
+    }
+
+    unsafe fn upload(program: GLuint, location: GLint, values: &[Self]) {
+        gl::ProgramUniform1iv(program, location, values.len() as i32, values.as_ptr());
+    }
+}
+
+/// Booleans are uploaded as integers, so they have to be converted first.
+impl UniformValue for bool {
+    const GLSL_TYPE: &'static str = "bool";
+
+    fn accepts(gl_type: GLenum) -> bool {
+        gl_type == gl::BOOL
+    }
+
+    unsafe fn upload(program: GLuint, location: GLint, values: &[Self]) {
+        let values: Vec<i32> = values.iter().map(|&b| b as i32).collect();
+        gl::ProgramUniform1iv(program, location, values.len() as i32, values.as_ptr());
+    }
+}
+
+/// Boolean vectors are converted to integer vectors, like `bool`.
+macro_rules! uniform_bvec {
+    ($type:ty, $glsl:expr, $gl_type:path, $function:ident) => {
+        impl UniformValue for $type {
+            const GLSL_TYPE: &'static str = $glsl;
+
+            fn accepts(gl_type: GLenum) -> bool {
+                gl_type == $gl_type
+            }
+
+            unsafe fn upload(program: GLuint, location: GLint, values: &[Self]) {
+                let components: Vec<i32> = values
+                    .iter()
+                    .flat_map(|vector| vector.iter().map(|&b| b as i32))
+                    .collect();
+                gl::$function(program, location, values.len() as i32, components.as_ptr());
+            }
+        }
+    };
+}
+
+uniform_bvec!(glm::BVec2, "bvec2", gl::BOOL_VEC2, ProgramUniform2iv);
+uniform_bvec!(glm::BVec3, "bvec3", gl::BOOL_VEC3, ProgramUniform3iv);
+uniform_bvec!(glm::BVec4, "bvec4", gl::BOOL_VEC4, ProgramUniform4iv);
+
+/// Samplers and images are set through their texture unit.
+fn is_opaque(gl_type: GLenum) -> bool {
+    matches!(
+        gl_type,
+        gl::SAMPLER_1D
+            | gl::SAMPLER_2D
+            | gl::SAMPLER_3D
+            | gl::SAMPLER_CUBE
+            | gl::SAMPLER_1D_SHADOW
+            | gl::SAMPLER_2D_SHADOW
+            | gl::SAMPLER_1D_ARRAY
+            | gl::SAMPLER_2D_ARRAY
+            | gl::SAMPLER_CUBE_MAP_ARRAY
+            | gl::SAMPLER_1D_ARRAY_SHADOW
+            | gl::SAMPLER_2D_ARRAY_SHADOW
+            | gl::SAMPLER_CUBE_SHADOW
+            | gl::SAMPLER_CUBE_MAP_ARRAY_SHADOW
+            | gl::SAMPLER_2D_MULTISAMPLE
+            | gl::SAMPLER_2D_MULTISAMPLE_ARRAY
+            | gl::SAMPLER_BUFFER
+            | gl::SAMPLER_2D_RECT
+            | gl::SAMPLER_2D_RECT_SHADOW
+            | gl::INT_SAMPLER_1D
+            | gl::INT_SAMPLER_2D
+            | gl::INT_SAMPLER_3D
+            | gl::INT_SAMPLER_CUBE
+            | gl::INT_SAMPLER_1D_ARRAY
+            | gl::INT_SAMPLER_2D_ARRAY
+            | gl::INT_SAMPLER_CUBE_MAP_ARRAY
+            | gl::INT_SAMPLER_2D_MULTISAMPLE
+            | gl::INT_SAMPLER_2D_MULTISAMPLE_ARRAY
+            | gl::INT_SAMPLER_BUFFER
+            | gl::INT_SAMPLER_2D_RECT
+            | gl::UNSIGNED_INT_SAMPLER_1D
+            | gl::UNSIGNED_INT_SAMPLER_2D
+            | gl::UNSIGNED_INT_SAMPLER_3D
+            | gl::UNSIGNED_INT_SAMPLER_CUBE
+            | gl::UNSIGNED_INT_SAMPLER_1D_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_2D_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_CUBE_MAP_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE
+            | gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_BUFFER
+            | gl::UNSIGNED_INT_SAMPLER_2D_RECT
+            | gl::IMAGE_1D
+            | gl::IMAGE_2D
+            | gl::IMAGE_3D
+            | gl::IMAGE_2D_RECT
+            | gl::IMAGE_CUBE
+            | gl::IMAGE_BUFFER
+            | gl::IMAGE_1D_ARRAY
+            | gl::IMAGE_2D_ARRAY
+            | gl::IMAGE_CUBE_MAP_ARRAY
+            | gl::IMAGE_2D_MULTISAMPLE
+            | gl::IMAGE_2D_MULTISAMPLE_ARRAY
+            | gl::INT_IMAGE_1D
+            | gl::INT_IMAGE_2D
+            | gl::INT_IMAGE_3D
+            | gl::INT_IMAGE_2D_RECT
+            | gl::INT_IMAGE_CUBE
+            | gl::INT_IMAGE_BUFFER
+            | gl::INT_IMAGE_1D_ARRAY
+            | gl::INT_IMAGE_2D_ARRAY
+            | gl::INT_IMAGE_CUBE_MAP_ARRAY
+            | gl::INT_IMAGE_2D_MULTISAMPLE
+            | gl::INT_IMAGE_2D_MULTISAMPLE_ARRAY
+            | gl::UNSIGNED_INT_IMAGE_1D
+            | gl::UNSIGNED_INT_IMAGE_2D
+            | gl::UNSIGNED_INT_IMAGE_3D
+            | gl::UNSIGNED_INT_IMAGE_2D_RECT
+            | gl::UNSIGNED_INT_IMAGE_CUBE
+            | gl::UNSIGNED_INT_IMAGE_BUFFER
+            | gl::UNSIGNED_INT_IMAGE_1D_ARRAY
+            | gl::UNSIGNED_INT_IMAGE_2D_ARRAY
+            | gl::UNSIGNED_INT_IMAGE_CUBE_MAP_ARRAY
+            | gl::UNSIGNED_INT_IMAGE_2D_MULTISAMPLE
+            | gl::UNSIGNED_INT_IMAGE_2D_MULTISAMPLE_ARRAY
+    )
+}
+
+/// GLSL name of a uniform type as reported by the driver.
+pub fn glsl_type_name(gl_type: GLenum) -> &'static str {
+    match gl_type {
+        gl::FLOAT => "float",
+        gl::FLOAT_VEC2 => "vec2",
+        gl::FLOAT_VEC3 => "vec3",
+        gl::FLOAT_VEC4 => "vec4",
+        gl::INT => "int",
+        gl::INT_VEC2 => "ivec2",
+        gl::INT_VEC3 => "ivec3",
+        gl::INT_VEC4 => "ivec4",
+        gl::UNSIGNED_INT => "uint",
+        gl::UNSIGNED_INT_VEC2 => "uvec2",
+        gl::UNSIGNED_INT_VEC3 => "uvec3",
+        gl::UNSIGNED_INT_VEC4 => "uvec4",
+        gl::BOOL => "bool",
+        gl::BOOL_VEC2 => "bvec2",
+        gl::BOOL_VEC3 => "bvec3",
+        gl::BOOL_VEC4 => "bvec4",
+        gl::FLOAT_MAT2 => "mat2",
+        gl::FLOAT_MAT3 => "mat3",
+        gl::FLOAT_MAT4 => "mat4",
+        gl::FLOAT_MAT2x3 => "mat2x3",
+        gl::FLOAT_MAT2x4 => "mat2x4",
+        gl::FLOAT_MAT3x2 => "mat3x2",
+        gl::FLOAT_MAT3x4 => "mat3x4",
+        gl::FLOAT_MAT4x2 => "mat4x2",
+        gl::FLOAT_MAT4x3 => "mat4x3",
+        gl::DOUBLE => "double",
+        t if is_opaque(t) => "sampler or image",
+        _ => "an unsupported type",
+    }
+}
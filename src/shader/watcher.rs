@@ -0,0 +1,65 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// How often the files are checked. Checking every frame would mean a lot of needless syscalls.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Watches shader sources for changes by polling their modification times.
+pub struct ShaderWatcher {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    last_poll: Instant,
+}
+
+impl ShaderWatcher {
+    pub fn new<P: AsRef<Path>>(paths: &[P]) -> ShaderWatcher {
+        let mut watcher = ShaderWatcher {
+            files: vec![],
+            last_poll: Instant::now(),
+        };
+        watcher.watch(paths);
+        watcher
+    }
+
+    /// Replace the set of watched files. Their current state is taken as unchanged.
+    pub fn watch<P: AsRef<Path>>(&mut self, paths: &[P]) {
+        self.files = paths
+            .iter()
+            .map(|path| (path.as_ref().to_path_buf(), modified(path.as_ref())))
+            .collect();
+    }
+
+    /// Start watching files that are not watched yet, without touching the state of the others.
+    pub fn add<P: AsRef<Path>>(&mut self, paths: &[P]) {
+        for path in paths {
+            let path = path.as_ref();
+            if !self.files.iter().any(|(watched, _)| watched == path) {
+                self.files.push((path.to_path_buf(), modified(path)));
+            }
+        }
+    }
+
+    /// The watched files that were modified, created or deleted since the last call.
+    pub fn changed_files(&mut self) -> Vec<PathBuf> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return vec![];
+        }
+        self.last_poll = Instant::now();
+
+        let mut changed = vec![];
+        for (path, last_modified) in &mut self.files {
+            let now_modified = modified(path);
+            if now_modified != *last_modified {
+                *last_modified = now_modified;
+                changed.push(path.clone());
+            }
+        }
+        changed
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -0,0 +1,439 @@
+use crate::mesh::MeshData;
+use glm::{vec2, vec3, Vec3};
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::fmt;
+
+/// Step used for the finite differences that give us normals.
+const DERIVATIVE_STEP: f32 = 1e-3;
+
+/// The edges of a unit cube as pairs of corners. Corner `i` has its x, y and z offsets in bits 0, 1 and 2.
+#[rustfmt::skip]
+const CUBE_EDGES: [(usize, usize); 12] = [
+    (0, 1), (2, 3), (4, 5), (6, 7), // along x
+    (0, 2), (1, 3), (4, 6), (5, 7), // along y
+    (0, 4), (1, 5), (2, 6), (3, 7), // along z
+];
+
+/// The corners of each cube face, counter-clockwise when seen from outside the cube.
+const CUBE_FACES: [[usize; 4]; 6] = [
+    [0, 4, 6, 2], // -x
+    [1, 3, 7, 5], // +x
+    [0, 1, 5, 4], // -y
+    [2, 6, 7, 3], // +y
+    [0, 2, 3, 1], // -z
+    [4, 5, 7, 6], // +z
+];
+
+/// A surface given by a function from the (u, v) plane into space.
+pub struct ParametricSurface {
+    function: Box<dyn Fn(f32, f32) -> Vec3>,
+    u_range: (f32, f32),
+    v_range: (f32, f32),
+    segments: (u32, u32),
+    double_sided: bool,
+}
+
+/// A surface given as the set of points where `f(x, y, z) = 0`.
+/// Points where the function is negative are considered inside.
+pub struct ImplicitSurface {
+    function: Box<dyn Fn(f32, f32, f32) -> f32>,
+    bounds: (Vec3, Vec3),
+    resolution: u32,
+}
+
+impl ParametricSurface {
+    pub fn new<F>(function: F, u_range: (f32, f32), v_range: (f32, f32)) -> ParametricSurface
+    where
+        F: Fn(f32, f32) -> Vec3 + 'static,
+    {
+        ParametricSurface {
+            function: Box::new(function),
+            u_range,
+            v_range,
+            segments: (64, 64),
+            double_sided: false,
+        }
+    }
+
+    /// A Möbius strip of radius 1 and half width `width`.
+    pub fn mobius_strip(width: f32) -> ParametricSurface {
+        ParametricSurface::new(
+            move |u, v| {
+                let v = v * width;
+                let r = 1.0 + v * (u / 2.0).cos();
+                vec3(r * u.cos(), v * (u / 2.0).sin(), r * u.sin())
+            },
+            (0.0, 2.0 * PI),
+            (-1.0, 1.0),
+        )
+        .segments(128, 16)
+        .double_sided(true)
+    }
+
+    /// The figure-8 immersion of the Klein bottle.
+    pub fn klein_bottle(radius: f32) -> ParametricSurface {
+        ParametricSurface::new(
+            move |u, v| {
+                let (half_sin, half_cos) = (u / 2.0).sin_cos();
+                let w = half_cos * v.sin() - half_sin * (2.0 * v).sin();
+                let r = radius + w;
+                vec3(
+                    r * u.cos(),
+                    half_sin * v.sin() + half_cos * (2.0 * v).sin(),
+                    r * u.sin(),
+                )
+            },
+            (0.0, 2.0 * PI),
+            (0.0, 2.0 * PI),
+        )
+        .segments(128, 64)
+        .double_sided(true)
+    }
+
+    /// The sine surface `(sin u, sin v, sin(u + v))`.
+    pub fn sine_surface() -> ParametricSurface {
+        ParametricSurface::new(
+            |u, v| vec3(u.sin(), v.sin(), (u + v).sin()),
+            (0.0, 2.0 * PI),
+            (0.0, 2.0 * PI),
+        )
+        .segments(96, 96)
+        .double_sided(true)
+    }
+
+    /// A square sheet of waves, `y = amplitude * sin(frequency * x) * cos(frequency * z)`.
+    pub fn sine_wave(size: f32, amplitude: f32, frequency: f32) -> ParametricSurface {
+        ParametricSurface::new(
+            move |u, v| {
+                let (x, z) = (u * size, v * size);
+                vec3(
+                    x,
+                    amplitude * (frequency * x).sin() * (frequency * z).cos(),
+                    z,
+                )
+            },
+            (-0.5, 0.5),
+            (0.5, -0.5),
+        )
+    }
+
+    /// Number of quads along u and v, at least 1 each.
+    pub fn segments(mut self, u_segments: u32, v_segments: u32) -> ParametricSurface {
+        self.segments = (u_segments, v_segments);
+        self
+    }
+
+    /// Also emit the back faces, for surfaces without a consistent outside.
+    pub fn double_sided(mut self, double_sided: bool) -> ParametricSurface {
+        self.double_sided = double_sided;
+        self
+    }
+
+    fn point(&self, u: f32, v: f32) -> Vec3 {
+        (self.function)(u, v)
+    }
+
+    /// Normal from the cross product of the partial derivatives.
+    /// Where the surface is degenerate (like the poles of a sphere) we step towards the middle of the domain.
+    fn normal(&self, u: f32, v: f32) -> Vec3 {
+        let u_center = (self.u_range.0 + self.u_range.1) / 2.0;
+        let v_center = (self.v_range.0 + self.v_range.1) / 2.0;
+        let (mut u, mut v) = (u, v);
+        for _ in 0..4 {
+            let du = (self.point(u + DERIVATIVE_STEP, v) - self.point(u - DERIVATIVE_STEP, v))
+                * (self.u_range.1 - self.u_range.0).signum();
+            let dv = (self.point(u, v + DERIVATIVE_STEP) - self.point(u, v - DERIVATIVE_STEP))
+                * (self.v_range.1 - self.v_range.0).signum();
+            let normal = du.cross(&dv);
+            if normal.norm() > f32::EPSILON {
+                return normal.normalize();
+            }
+            u += (u_center - u) * 0.01;
+            v += (v_center - v) * 0.01;
+        }
+        vec3(0.0, 1.0, 0.0)
+    }
+
+    pub fn build(&self) -> Result<MeshData, SurfaceError> {
+        let (u_segments, v_segments) = self.segments;
+        if u_segments == 0 || v_segments == 0 {
+            return Err(SurfaceError::NoSegments {
+                u_segments,
+                v_segments,
+            });
+        }
+        let mut mesh = MeshData::new();
+
+        let sides: &[bool] = if self.double_sided {
+            &[false, true]
+        } else {
+            &[false]
+        };
+        for &back in sides {
+            let first = mesh.vertex_count();
+            for j in 0..=v_segments {
+                for i in 0..=u_segments {
+                    let s = i as f32 / u_segments as f32;
+                    let t = j as f32 / v_segments as f32;
+                    let u = self.u_range.0 + (self.u_range.1 - self.u_range.0) * s;
+                    let v = self.v_range.0 + (self.v_range.1 - self.v_range.0) * t;
+                    let normal = self.normal(u, v);
+                    let normal = if back { -normal } else { normal };
+                    mesh.push_vertex(&self.point(u, v), &normal, &vec2(s, t));
+                }
+            }
+
+            let row = u_segments + 1;
+            for j in 0..v_segments {
+                for i in 0..u_segments {
+                    let a = first + j * row + i;
+                    let b = a + 1;
+                    let c = a + row;
+                    let d = c + 1;
+                    if back {
+                        mesh.push_triangle(a, c, b);
+                        mesh.push_triangle(b, c, d);
+                    } else {
+                        mesh.push_triangle(a, b, c);
+                        mesh.push_triangle(b, d, c);
+                    }
+                }
+            }
+        }
+
+        Ok(mesh)
+    }
+}
+
+impl ImplicitSurface {
+    pub fn new<F>(function: F, min: Vec3, max: Vec3) -> ImplicitSurface
+    where
+        F: Fn(f32, f32, f32) -> f32 + 'static,
+    {
+        ImplicitSurface {
+            function: Box::new(function),
+            bounds: (min, max),
+            resolution: 48,
+        }
+    }
+
+    pub fn sphere(radius: f32) -> ImplicitSurface {
+        let extent = vec3(radius, radius, radius) * 1.1;
+        ImplicitSurface::new(
+            move |x, y, z| (x * x + y * y + z * z).sqrt() - radius,
+            -extent,
+            extent,
+        )
+    }
+
+    /// A torus around the y axis.
+    pub fn torus(major_radius: f32, minor_radius: f32) -> ImplicitSurface {
+        let reach = (major_radius + minor_radius) * 1.1;
+        let height = minor_radius * 1.1;
+        ImplicitSurface::new(
+            move |x, y, z| {
+                let ring = (x * x + z * z).sqrt() - major_radius;
+                (ring * ring + y * y).sqrt() - minor_radius
+            },
+            vec3(-reach, -height, -reach),
+            vec3(reach, height, reach),
+        )
+    }
+
+    /// A gyroid minimal surface filling a cube of the given size.
+    pub fn gyroid(size: f32, periods: f32) -> ImplicitSurface {
+        let scale = 2.0 * PI * periods / size;
+        let extent = vec3(size, size, size) / 2.0;
+        ImplicitSurface::new(
+            move |x, y, z| {
+                let (x, y, z) = (x * scale, y * scale, z * scale);
+                x.sin() * y.cos() + y.sin() * z.cos() + z.sin() * x.cos()
+            },
+            -extent,
+            extent,
+        )
+        .resolution(64)
+    }
+
+    /// Number of cells along each axis of the bounding box, at least 1.
+    pub fn resolution(mut self, cells: u32) -> ImplicitSurface {
+        self.resolution = cells;
+        self
+    }
+
+    fn value(&self, p: &Vec3) -> f32 {
+        (self.function)(p.x, p.y, p.z)
+    }
+
+    /// The gradient points away from the inside, so it doubles as the surface normal.
+    fn normal(&self, p: &Vec3) -> Vec3 {
+        let h = DERIVATIVE_STEP;
+        let gradient = vec3(
+            self.value(&(p + vec3(h, 0.0, 0.0))) - self.value(&(p - vec3(h, 0.0, 0.0))),
+            self.value(&(p + vec3(0.0, h, 0.0))) - self.value(&(p - vec3(0.0, h, 0.0))),
+            self.value(&(p + vec3(0.0, 0.0, h))) - self.value(&(p - vec3(0.0, 0.0, h))),
+        );
+        if gradient.norm() > f32::EPSILON {
+            gradient.normalize()
+        } else {
+            vec3(0.0, 1.0, 0.0)
+        }
+    }
+
+    /// Mesh the surface with marching cubes. Vertices on shared cube edges are reused.
+    pub fn build(&self) -> Result<MeshData, SurfaceError> {
+        if self.resolution == 0 {
+            return Err(SurfaceError::NoCells);
+        }
+        let cases = marching_cubes_cases();
+        let n = self.resolution as usize;
+        let points = n + 1;
+        let (min, max) = self.bounds;
+        let cell = (max - min) / n as f32;
+        let position = |x: usize, y: usize, z: usize| {
+            min + vec3(x as f32 * cell.x, y as f32 * cell.y, z as f32 * cell.z)
+        };
+
+        let mut values = Vec::with_capacity(points * points * points);
+        for z in 0..points {
+            for y in 0..points {
+                for x in 0..points {
+                    values.push(self.value(&position(x, y, z)));
+                }
+            }
+        }
+        let index = |x: usize, y: usize, z: usize| (z * points + y) * points + x;
+
+        let mut mesh = MeshData::new();
+        let mut edge_vertices: HashMap<(usize, usize), u32> = HashMap::new();
+
+        for z in 0..n {
+            for y in 0..n {
+                for x in 0..n {
+                    let corner = |c: usize| (x + (c & 1), y + ((c >> 1) & 1), z + ((c >> 2) & 1));
+
+                    let mut case = 0;
+                    for c in 0..8 {
+                        let (cx, cy, cz) = corner(c);
+                        if values[index(cx, cy, cz)] < 0.0 {
+                            case |= 1 << c;
+                        }
+                    }
+
+                    let mut vertex_on_edge = |edge: usize, mesh: &mut MeshData| {
+                        let (a, b) = CUBE_EDGES[edge];
+                        let (ax, ay, az) = corner(a);
+                        let key = (index(ax, ay, az), edge / 4);
+                        *edge_vertices.entry(key).or_insert_with(|| {
+                            let (bx, by, bz) = corner(b);
+                            let value_a = values[index(ax, ay, az)];
+                            let value_b = values[index(bx, by, bz)];
+                            let t = -value_a / (value_b - value_a);
+                            let p = glm::lerp(&position(ax, ay, az), &position(bx, by, bz), t);
+                            let uv = vec2(
+                                (p.x - min.x) / (max.x - min.x),
+                                (p.z - min.z) / (max.z - min.z),
+                            );
+                            mesh.push_vertex(&p, &self.normal(&p), &uv)
+                        })
+                    };
+
+                    for triangle in &cases[case] {
+                        let a = vertex_on_edge(triangle[0], &mut mesh);
+                        let b = vertex_on_edge(triangle[1], &mut mesh);
+                        let c = vertex_on_edge(triangle[2], &mut mesh);
+                        mesh.push_triangle(a, b, c);
+                    }
+                }
+            }
+        }
+
+        Ok(mesh)
+    }
+}
+
+/// Derive the marching cubes triangle table instead of hard-coding it.
+///
+/// For every face, the edge crossings are connected so that inside corners are cut off from each other.
+/// This resolves ambiguous faces the same way from both neighbouring cubes, so the result is watertight.
+/// Chaining the segments gives closed loops around each piece of surface, which are then fanned into
+/// triangles facing away from the inside.
+fn marching_cubes_cases() -> Vec<Vec<[usize; 3]>> {
+    let edge_between = |a: usize, b: usize| {
+        CUBE_EDGES
+            .iter()
+            .position(|&(x, y)| (x, y) == (a.min(b), a.max(b)))
+            .unwrap()
+    };
+
+    (0..256usize)
+        .map(|case| {
+            let inside = |corner: usize| case & (1 << corner) != 0;
+
+            // next[edge] is the crossing that follows `edge` along its loop
+            let mut next: [Option<usize>; 12] = [None; 12];
+            for face in &CUBE_FACES {
+                let crossings: Vec<(usize, bool)> = (0..4)
+                    .filter_map(|i| {
+                        let (a, b) = (face[i], face[(i + 1) % 4]);
+                        if inside(a) != inside(b) {
+                            Some((edge_between(a, b), inside(a)))
+                        } else {
+                            None
+                        }
+                    })
+                    .collect();
+                for (i, &(edge, is_exit)) in crossings.iter().enumerate() {
+                    if is_exit {
+                        let entry = crossings[(i + crossings.len() - 1) % crossings.len()].0;
+                        next[edge] = Some(entry);
+                    }
+                }
+            }
+
+            let mut triangles = vec![];
+            let mut visited = [false; 12];
+            for start in 0..12 {
+                if visited[start] || next[start].is_none() {
+                    continue;
+                }
+                let mut polygon = vec![];
+                let mut edge = start;
+                while !visited[edge] {
+                    visited[edge] = true;
+                    polygon.push(edge);
+                    edge = next[edge].unwrap();
+                }
+                // The loops wind around the inside, so the fan is reversed to face outwards
+                for i in 1..polygon.len() - 1 {
+                    triangles.push([polygon[0], polygon[i + 1], polygon[i]]);
+                }
+            }
+            triangles
+        })
+        .collect()
+}
+
+/// Settings a surface cannot be meshed with.
+#[derive(Debug)]
+pub enum SurfaceError {
+    NoSegments { u_segments: u32, v_segments: u32 },
+    NoCells,
+}
+
+impl fmt::Display for SurfaceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SurfaceError::NoSegments {
+                u_segments,
+                v_segments,
+            } => write!(
+                f,
+                "A surface needs at least one segment along u and v, but was given {}x{}",
+                u_segments, v_segments
+            ),
+            SurfaceError::NoCells => write!(f, "The grid needs at least one cell"),
+        }
+    }
+}
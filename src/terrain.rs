@@ -0,0 +1,345 @@
+use crate::mesh::MeshData;
+use image::GrayImage;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use std::fmt;
+
+const NOISE_TABLE_SIZE: usize = 256;
+
+/// Where the terrain heights come from. Both sources yield heights in the range [0, 1].
+pub enum HeightSource {
+    /// A grayscale heightmap, black being the lowest point and white the highest.
+    Image(GrayImage),
+    /// Fractal value noise made of several octaves.
+    Noise {
+        seed: u64,
+        octaves: u32,
+        frequency: f32,
+        persistence: f32,
+    },
+}
+
+/// Builds a grid mesh from a height source. The grid is split into square chunks so large
+/// terrains can be culled or streamed per chunk later on.
+pub struct TerrainBuilder {
+    source: HeightSource,
+    resolution: (u32, u32),
+    size: (f32, f32),
+    vertical_scale: f32,
+    chunk_size: u32,
+}
+
+/// A finished terrain. Chunks share their border vertices, so there are no visible seams.
+pub struct Terrain {
+    pub chunks: Vec<MeshData>,
+    pub resolution: (u32, u32),
+    pub size: (f32, f32),
+    heights: Vec<f32>,
+}
+
+impl TerrainBuilder {
+    /// Use an image as heightmap. Color images are converted to grayscale.
+    pub fn from_image(path: &str) -> image::ImageResult<TerrainBuilder> {
+        let heightmap = image::open(path)?.into_luma8();
+        let resolution = heightmap.dimensions();
+        Ok(TerrainBuilder::new(
+            HeightSource::Image(heightmap),
+            resolution,
+        ))
+    }
+
+    /// Use fractal noise as heightmap. The same seed always produces the same terrain.
+    pub fn from_noise(seed: u64) -> TerrainBuilder {
+        TerrainBuilder::new(
+            HeightSource::Noise {
+                seed,
+                octaves: 5,
+                frequency: 4.0,
+                persistence: 0.5,
+            },
+            (129, 129),
+        )
+    }
+
+    fn new(source: HeightSource, resolution: (u32, u32)) -> TerrainBuilder {
+        TerrainBuilder {
+            source,
+            resolution,
+            size: (10.0, 10.0),
+            vertical_scale: 1.0,
+            chunk_size: 32,
+        }
+    }
+
+    /// Number of samples along x and z, at least 2 each. The heightmap is resampled if it does
+    /// not match.
+    pub fn resolution(mut self, x_samples: u32, z_samples: u32) -> TerrainBuilder {
+        self.resolution = (x_samples, z_samples);
+        self
+    }
+
+    /// Horizontal extent of the terrain in world units. The terrain is centered on the origin.
+    pub fn size(mut self, width: f32, depth: f32) -> TerrainBuilder {
+        self.size = (width, depth);
+        self
+    }
+
+    /// Height of the tallest possible point in world units.
+    pub fn vertical_scale(mut self, scale: f32) -> TerrainBuilder {
+        self.vertical_scale = scale;
+        self
+    }
+
+    /// Number of grid cells along each side of a chunk, at least 1.
+    pub fn chunk_size(mut self, cells: u32) -> TerrainBuilder {
+        self.chunk_size = cells;
+        self
+    }
+
+    /// Configure the noise octaves. Has no effect on image heightmaps.
+    pub fn octaves(mut self, count: u32, frequency: f32, persistence: f32) -> TerrainBuilder {
+        if let HeightSource::Noise {
+            octaves: o,
+            frequency: f,
+            persistence: p,
+            ..
+        } = &mut self.source
+        {
+            *o = count.max(1);
+            *f = frequency;
+            *p = persistence;
+        }
+        self
+    }
+
+    pub fn build(&self) -> Result<Terrain, TerrainError> {
+        let (x_samples, z_samples) = self.resolution;
+        if x_samples < 2 || z_samples < 2 {
+            return Err(TerrainError::TooFewSamples {
+                x_samples,
+                z_samples,
+            });
+        }
+        if self.chunk_size == 0 {
+            return Err(TerrainError::EmptyChunks);
+        }
+        let heights = self.sample_heights();
+
+        let mut terrain = Terrain {
+            chunks: vec![],
+            resolution: self.resolution,
+            size: self.size,
+            heights,
+        };
+
+        let mut z_start = 0;
+        while z_start < z_samples - 1 {
+            let z_end = (z_start + self.chunk_size).min(z_samples - 1);
+            let mut x_start = 0;
+            while x_start < x_samples - 1 {
+                let x_end = (x_start + self.chunk_size).min(x_samples - 1);
+                let chunk = terrain.build_chunk(x_start, x_end, z_start, z_end);
+                terrain.chunks.push(chunk);
+                x_start = x_end;
+            }
+            z_start = z_end;
+        }
+
+        Ok(terrain)
+    }
+
+    /// Sample the height source on the grid and scale it to world units.
+    fn sample_heights(&self) -> Vec<f32> {
+        let (x_samples, z_samples) = self.resolution;
+        let mut heights = Vec::with_capacity((x_samples * z_samples) as usize);
+        match &self.source {
+            HeightSource::Image(heightmap) => {
+                for z in 0..z_samples {
+                    for x in 0..x_samples {
+                        let u = x as f32 / (x_samples - 1) as f32;
+                        let v = z as f32 / (z_samples - 1) as f32;
+                        heights.push(sample_image(heightmap, u, v) * self.vertical_scale);
+                    }
+                }
+            }
+            HeightSource::Noise {
+                seed,
+                octaves,
+                frequency,
+                persistence,
+            } => {
+                let noise = ValueNoise::new(*seed);
+                for z in 0..z_samples {
+                    for x in 0..x_samples {
+                        let u = x as f32 / (x_samples - 1) as f32;
+                        let v = z as f32 / (z_samples - 1) as f32;
+                        let height = noise.fractal(u, v, *octaves, *frequency, *persistence);
+                        heights.push(height * self.vertical_scale);
+                    }
+                }
+            }
+        }
+        heights
+    }
+}
+
+impl Terrain {
+    /// All chunks in one mesh, for drawing the terrain as a whole.
+    pub fn mesh(&self) -> MeshData {
+        let mut mesh = MeshData::new();
+        for chunk in &self.chunks {
+            mesh.append(chunk);
+        }
+        mesh
+    }
+
+    /// Height at grid sample (x, z), clamped to the edges of the grid.
+    pub fn height(&self, x: i64, z: i64) -> f32 {
+        let x = x.clamp(0, self.resolution.0 as i64 - 1) as usize;
+        let z = z.clamp(0, self.resolution.1 as i64 - 1) as usize;
+        self.heights[z * self.resolution.0 as usize + x]
+    }
+
+    fn spacing(&self) -> (f32, f32) {
+        (
+            self.size.0 / (self.resolution.0 - 1) as f32,
+            self.size.1 / (self.resolution.1 - 1) as f32,
+        )
+    }
+
+    /// Normal from the central differences of the height field.
+    fn normal(&self, x: i64, z: i64) -> glm::Vec3 {
+        let (dx, dz) = self.spacing();
+        let slope_x = (self.height(x + 1, z) - self.height(x - 1, z)) / (2.0 * dx);
+        let slope_z = (self.height(x, z + 1) - self.height(x, z - 1)) / (2.0 * dz);
+        glm::normalize(&glm::vec3(-slope_x, 1.0, -slope_z))
+    }
+
+    fn build_chunk(&self, x_start: u32, x_end: u32, z_start: u32, z_end: u32) -> MeshData {
+        let (dx, dz) = self.spacing();
+        let (x_samples, z_samples) = self.resolution;
+        let mut mesh = MeshData::new();
+
+        for z in z_start..=z_end {
+            for x in x_start..=x_end {
+                let (xi, zi) = (x as i64, z as i64);
+                let position = glm::vec3(
+                    x as f32 * dx - self.size.0 / 2.0,
+                    self.height(xi, zi),
+                    z as f32 * dz - self.size.1 / 2.0,
+                );
+                let uv = glm::vec2(
+                    x as f32 / (x_samples - 1) as f32,
+                    z as f32 / (z_samples - 1) as f32,
+                );
+                mesh.push_vertex(&position, &self.normal(xi, zi), &uv);
+            }
+        }
+
+        // Counter-clockwise when seen from above
+        let row = x_end - x_start + 1;
+        for z in 0..(z_end - z_start) {
+            for x in 0..(x_end - x_start) {
+                let top_left = z * row + x;
+                let top_right = top_left + 1;
+                let bottom_left = top_left + row;
+                let bottom_right = bottom_left + 1;
+                mesh.push_triangle(top_left, bottom_left, top_right);
+                mesh.push_triangle(top_right, bottom_left, bottom_right);
+            }
+        }
+
+        mesh
+    }
+}
+
+/// Bilinearly sample a grayscale image at normalized coordinates.
+fn sample_image(image: &GrayImage, u: f32, v: f32) -> f32 {
+    let (width, height) = image.dimensions();
+    let x = u * (width - 1) as f32;
+    let y = v * (height - 1) as f32;
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+    let pixel = |x: u32, y: u32| image.get_pixel(x, y).0[0] as f32 / 255.0;
+    let top = lerp(pixel(x0, y0), pixel(x1, y0), tx);
+    let bottom = lerp(pixel(x0, y1), pixel(x1, y1), tx);
+    lerp(top, bottom, ty)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// Lattice value noise backed by a shuffled permutation table.
+struct ValueNoise {
+    permutation: Vec<usize>,
+    values: Vec<f32>,
+}
+
+impl ValueNoise {
+    fn new(seed: u64) -> ValueNoise {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut permutation: Vec<usize> = (0..NOISE_TABLE_SIZE).collect();
+        permutation.shuffle(&mut rng);
+        let values = (0..NOISE_TABLE_SIZE).map(|_| rng.gen::<f32>()).collect();
+        ValueNoise {
+            permutation,
+            values,
+        }
+    }
+
+    fn lattice(&self, x: i64, z: i64) -> f32 {
+        let wrap = |i: i64| i.rem_euclid(NOISE_TABLE_SIZE as i64) as usize;
+        let hash = self.permutation[(self.permutation[wrap(x)] + wrap(z)) % NOISE_TABLE_SIZE];
+        self.values[hash]
+    }
+
+    /// Smoothly interpolated noise in the range [0, 1].
+    fn sample(&self, x: f32, z: f32) -> f32 {
+        let (x0, z0) = (x.floor(), z.floor());
+        let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+        let (tx, tz) = (smooth(x - x0), smooth(z - z0));
+        let (x0, z0) = (x0 as i64, z0 as i64);
+        let top = lerp(self.lattice(x0, z0), self.lattice(x0 + 1, z0), tx);
+        let bottom = lerp(self.lattice(x0, z0 + 1), self.lattice(x0 + 1, z0 + 1), tx);
+        lerp(top, bottom, tz)
+    }
+
+    /// Sum of octaves with doubling frequency, normalized back to [0, 1].
+    fn fractal(&self, x: f32, z: f32, octaves: u32, frequency: f32, persistence: f32) -> f32 {
+        let mut total = 0.0;
+        let mut amplitude = 1.0;
+        let mut max_value = 0.0;
+        let mut frequency = frequency;
+        for _ in 0..octaves {
+            total += self.sample(x * frequency, z * frequency) * amplitude;
+            max_value += amplitude;
+            amplitude *= persistence;
+            frequency *= 2.0;
+        }
+        total / max_value
+    }
+}
+
+/// Settings a terrain cannot be built with.
+#[derive(Debug)]
+pub enum TerrainError {
+    TooFewSamples { x_samples: u32, z_samples: u32 },
+    EmptyChunks,
+}
+
+impl fmt::Display for TerrainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TerrainError::TooFewSamples {
+                x_samples,
+                z_samples,
+            } => write!(
+                f,
+                "Terrain needs at least 2x2 samples, but was given {}x{}",
+                x_samples, z_samples
+            ),
+            TerrainError::EmptyChunks => write!(f, "Terrain chunks need at least one cell"),
+        }
+    }
+}
@@ -5,6 +5,23 @@ pub unsafe fn get_gl_string(name: gl::types::GLenum) -> String {
     std::ffi::CStr::from_ptr(gl::GetString(name) as *mut libc::c_char).to_string_lossy().to_string()
 }
 
+// Whether the current context is at least OpenGL `major.minor`, or lists `extension`,
+// like "GL_ARB_clip_control"
+pub unsafe fn has_version_or_extension(major: i32, minor: i32, extension: &str) -> bool {
+    let (mut context_major, mut context_minor) = (0, 0);
+    gl::GetIntegerv(gl::MAJOR_VERSION, &mut context_major);
+    gl::GetIntegerv(gl::MINOR_VERSION, &mut context_minor);
+    if (context_major, context_minor) >= (major, minor) {
+        return true;
+    }
+    let mut extensions = 0;
+    gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut extensions);
+    (0..extensions as u32).any(|index| {
+        let name = gl::GetStringi(gl::EXTENSIONS, index) as *const libc::c_char;
+        !name.is_null() && std::ffi::CStr::from_ptr(name).to_bytes() == extension.as_bytes()
+    })
+}
+
 // Debug callback to panic upon enountering any OpenGL error
 pub extern "system" fn debug_callback(
     source: u32, e_type: u32, id: u32,
@@ -0,0 +1,203 @@
+use crate::gl_object::{Buffer, VertexArray};
+use gl::types::GLenum;
+
+/// The names vertex attributes can have, and the location each name is bound to.
+/// `ShaderBuilder::link` binds the vertex shader inputs of every program to these locations
+/// by name, so any vertex array can be drawn with any program.
+pub const ATTRIBUTES: [(&str, u32); 4] = [("position", 0), ("normal", 1), ("color", 2), ("uv", 3)];
+
+/// The location of the vertex attribute named `name`, if it is in `ATTRIBUTES`.
+pub fn attribute_location(name: &str) -> Option<u32> {
+    ATTRIBUTES
+        .iter()
+        .find(|(attribute, _)| *attribute == name)
+        .map(|&(_, location)| location)
+}
+
+/// The type of each component of a vertex attribute, as stored in the buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComponentType {
+    F32,
+    /// Half floats, stored as their bits in a `u16`.
+    F16,
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+}
+
+impl ComponentType {
+    /// Size of one component in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            ComponentType::I8 | ComponentType::U8 => 1,
+            ComponentType::F16 | ComponentType::I16 | ComponentType::U16 => 2,
+            ComponentType::F32 | ComponentType::I32 | ComponentType::U32 => 4,
+        }
+    }
+}
+
+impl From<ComponentType> for GLenum {
+    fn from(component_type: ComponentType) -> GLenum {
+        match component_type {
+            ComponentType::F32 => gl::FLOAT,
+            ComponentType::F16 => gl::HALF_FLOAT,
+            ComponentType::I8 => gl::BYTE,
+            ComponentType::U8 => gl::UNSIGNED_BYTE,
+            ComponentType::I16 => gl::SHORT,
+            ComponentType::U16 => gl::UNSIGNED_SHORT,
+            ComponentType::I32 => gl::INT,
+            ComponentType::U32 => gl::UNSIGNED_INT,
+        }
+    }
+}
+
+/// One input of the vertex shader, matched to the shader by name. The shader sees floats,
+/// so integer components are converted as they are.
+#[derive(Clone, Debug)]
+pub struct VertexAttribute {
+    // The location the attribute's name is bound to
+    location: u32,
+    component_type: ComponentType,
+    components: i32,
+}
+
+impl VertexAttribute {
+    /// `components` values of `component_type` for the shader input `name`.
+    /// The name has to be one of `ATTRIBUTES`.
+    pub fn new(name: &str, component_type: ComponentType, components: i32) -> VertexAttribute {
+        let location = attribute_location(name).unwrap_or_else(|| {
+            panic!(
+                "There is no vertex attribute named {}, add it to ATTRIBUTES",
+                name
+            )
+        });
+        VertexAttribute {
+            location,
+            component_type,
+            components,
+        }
+    }
+
+    /// `components` 32 bit floats, the most common case.
+    pub fn float(name: &str, components: i32) -> VertexAttribute {
+        VertexAttribute::new(name, ComponentType::F32, components)
+    }
+
+    /// Size of the attribute in bytes.
+    pub fn size(&self) -> usize {
+        self.component_type.size() * self.components as usize
+    }
+}
+
+/// Attributes that are interleaved in one buffer, in order.
+///
+/// Each attribute is aligned to the size of its components, so a stream has the same layout
+/// as a `#[repr(C)]` struct with the attributes as fields.
+#[derive(Clone, Debug, Default)]
+pub struct VertexStream {
+    attributes: Vec<VertexAttribute>,
+}
+
+impl VertexStream {
+    /// A stream with the next element for every vertex.
+    pub fn per_vertex() -> VertexStream {
+        VertexStream::default()
+    }
+
+    pub fn attribute(mut self, attribute: VertexAttribute) -> VertexStream {
+        self.attributes.push(attribute);
+        self
+    }
+
+    /// The byte offset of each attribute into an element.
+    pub fn offsets(&self) -> Vec<usize> {
+        let mut offsets = vec![];
+        let mut offset = 0;
+        for attribute in &self.attributes {
+            offset = align(offset, attribute.component_type.size());
+            offsets.push(offset);
+            offset += attribute.size();
+        }
+        offsets
+    }
+
+    /// The size of one element in bytes, padding included.
+    pub fn stride(&self) -> usize {
+        let end = match (self.offsets().last(), self.attributes.last()) {
+            (Some(offset), Some(attribute)) => offset + attribute.size(),
+            _ => 0,
+        };
+        let alignment = self
+            .attributes
+            .iter()
+            .map(|attribute| attribute.component_type.size())
+            .max()
+            .unwrap_or(1);
+        align(end, alignment)
+    }
+}
+
+/// Describes how vertex data is laid out in one or more buffers, so it can be uploaded
+/// without writing the GL calls for each new format.
+///
+/// ```ignore
+/// let layout = VertexLayout::new()
+///     .stream(VertexStream::per_vertex()
+///         .attribute(VertexAttribute::float("position", 3))
+///         .attribute(VertexAttribute::new("normal", ComponentType::F16, 4)))
+///     .stream(VertexStream::per_vertex()
+///         .attribute(VertexAttribute::float("uv", 2)));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct VertexLayout {
+    streams: Vec<VertexStream>,
+}
+
+impl VertexLayout {
+    pub fn new() -> VertexLayout {
+        VertexLayout::default()
+    }
+
+    /// Add a stream, read from the buffer at the same position as the stream.
+    pub fn stream(mut self, stream: VertexStream) -> VertexLayout {
+        self.streams.push(stream);
+        self
+    }
+
+    /// A vertex array reading `buffers` with this layout, one buffer per stream.
+    ///
+    /// Every attribute is set up at the location its name is bound to in `ATTRIBUTES`, so the
+    /// vertex array can be drawn with any program. Attributes a program does not read are
+    /// ignored. Needs a current GL context.
+    pub unsafe fn vertex_array(&self, buffers: Vec<Buffer>) -> VertexArray {
+        assert_eq!(
+            buffers.len(),
+            self.streams.len(),
+            "The vertex layout has {} streams, but was given {} buffers",
+            self.streams.len(),
+            buffers.len()
+        );
+
+        let mut vao = VertexArray::new();
+        for (stream, buffer) in self.streams.iter().zip(buffers) {
+            let binding = vao.add_vertex_buffer(buffer, stream.stride());
+            for (attribute, offset) in stream.attributes.iter().zip(stream.offsets()) {
+                vao.attribute(
+                    attribute.location,
+                    binding,
+                    attribute.components,
+                    attribute.component_type.into(),
+                    offset as u32,
+                );
+            }
+        }
+        vao
+    }
+}
+
+fn align(offset: usize, alignment: usize) -> usize {
+    offset.div_ceil(alignment) * alignment
+}